@@ -1,24 +1,34 @@
 // app_state.rs
+use crate::arrangement::{Arrangement, Clip, Pattern};
 use crate::audio::audio_player;
 use crate::cursor::Cursor;
 use crate::draw_components::ViewportDrawResult;
-use crate::loop_state::LoopState;
-use crate::pitch::{Pitch, Tone};
+use crate::loop_state::{LoopMode, LoopState};
+use crate::marker;
+use crate::metronome::Metronome;
+use crate::midi_input;
+use crate::midi_output::{self, ClockSync};
+use crate::pitch::{Pitch, Tone, OCTAVE_MAX};
 use crate::player::Player;
 use crate::resolution::Resolution;
-use crate::score::Score;
-use crate::score_viewport::ScoreViewport;
+use crate::score::{Note, Score, BAR_B32, DEFAULT_VELOCITY};
+use crate::score_viewport::{FollowMode, ScoreViewport};
+use crate::selection_range::{SelectionMode, SelectionRange};
+use crate::zoom::Zoom;
 use crate::{
     cursor::CursorMode,
     draw_components::{
-        self, score_draw_component::ScoreDrawComponent, status_bar_component::StatusBarComponent,
-        BoxDrawComponent, DrawComponent, DrawResult, NullComponent, Position, VSplitDrawComponent,
-        Window,
+        self, arrangement_component::ArrangementComponent, event_log_component::EventLogComponent, key_check_component::KeyCheckComponent,
+        mixer_component::MixerComponent,
+        note_inspector_component::NoteInspectorComponent, overview_component::{self, OverviewComponent},
+        panel_component::PanelComponent, score_draw_component::ScoreDrawComponent,
+        status_bar_component::StatusBarComponent, BoxDrawComponent, Cell, DrawComponent,
+        DrawResult, Position, VSplitDrawComponent, Window,
     },
 };
 use crate::{
     events::{capture_input, InputEvent},
-    selection_buffer::SelectionBuffer,
+    selection_buffer::{next_paste_repeat, PasteMode, SelectionBuffer},
 };
 use crossterm::{
     cursor::{self},
@@ -30,7 +40,18 @@ use std::io::{self, Write};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use crate::event_log::EventLog;
+use crate::grab::Grab;
+use crate::history::{History, Snapshot};
+use crate::mixer::Mixer;
+use crate::osc52;
+use crate::panel_view::PanelView;
+use crate::quantize::{Humanize, Quantize};
+use crate::recorder::Recorder;
+use crate::registers::Registers;
 use crate::song_file::SongFile;
+use crate::theme::Theme;
+use crate::transform::Transform;
 use log::error;
 
 pub struct AppState {
@@ -41,24 +62,71 @@ pub struct AppState {
     input_rx: mpsc::Receiver<InputEvent>,
     input_thread: Option<JoinHandle<()>>,
     audio_thread: Option<JoinHandle<()>>,
-    buffer: Option<Vec<Vec<char>>>,
+    buffer: Option<Vec<Vec<Cell>>>,
     cursor: Cursor,
     selection_buffer: SelectionBuffer,
     viewport_draw_result: Option<ViewportDrawResult>,
     loop_state: LoopState,
     song_file: SongFile,
+    theme: Theme,
+    panel_view: PanelView,
+    mixer: Mixer,
+    event_log: EventLog,
+    overview_bar: usize,
+    scale_snap: bool,
+    history: History,
+    quantize: Quantize,
+    humanize: Humanize,
+    grab: Option<Grab>,
+    selection_mode: SelectionMode,
+    registers: Registers,
+    pending_register: Option<char>,
+    paste_mode: PasteMode,
+    paste_repeat: u64,
+    pattern_index: usize,
+    pattern_edit: Option<PatternEdit>,
+    step_entry: bool,
+    step_chord: Vec<(Pitch, u8)>,
+    held_pitches: Vec<Pitch>,
+    recorder: Option<Recorder>,
+    record_quantize: bool,
+    clock_sync: ClockSync,
+    keys_mode: bool,
+    keys_octave: u16,
+    keys_held: Vec<(u8, Pitch)>, // Computer keyboard keys down, with the pitch they started
+    metronome: Metronome,
+    return_on_stop: bool,
+    // Cursor time and zoom at the last draw. The view only goes to the cursor when they
+    // change, so it doesn't fight the playhead.
+    drawn_cursor: Option<(u64, Zoom)>,
+}
+
+// The song set aside while one of its patterns is edited in its place.
+struct PatternEdit {
+    name: String,
+    length_b32: u64,
+    song: Score,
+    history: History,
+    cursor: Cursor,
 }
 
 impl AppState {
-    pub fn new(score: Arc<Mutex<Score>>) -> AppState {
+    pub fn new(score: Arc<Mutex<Score>>, loop_state: LoopState) -> AppState {
         let (tx, rx) = mpsc::channel();
 
-        let player = Player::create(Arc::clone(&score), 44100);
+        let mut player = Player::create(Arc::clone(&score), 44100);
+        player.set_loop_state(loop_state);
         let shared_player = Arc::new(Mutex::new(player));
 
         AppState {
             score,
-            score_viewport: ScoreViewport::new(Pitch::new(Tone::C, 4), Resolution::Time1_16, 0, 0),
+            score_viewport: ScoreViewport::new(
+                Pitch::new(Tone::C, 4),
+                Resolution::Time1_16,
+                Zoom::Time1_16,
+                0,
+                0,
+            ),
             player: shared_player,
             input_tx: tx,
             input_rx: rx,
@@ -68,8 +136,37 @@ impl AppState {
             cursor: Cursor::new(Pitch::new(Tone::C, 4), 0),
             selection_buffer: SelectionBuffer::None,
             viewport_draw_result: None,
-            loop_state: LoopState::new(),
+            loop_state,
             song_file: SongFile::new(),
+            theme: Theme::detect(),
+            panel_view: PanelView::Mixer,
+            mixer: Mixer::new(),
+            event_log: EventLog::new(),
+            overview_bar: 0,
+            scale_snap: false,
+            history: History::new(),
+            quantize: Quantize::new(Resolution::Time1_16),
+            humanize: Humanize::new(),
+            grab: None,
+            selection_mode: SelectionMode::Onset,
+            registers: Registers::new(),
+            pending_register: None,
+            paste_mode: PasteMode::Merge,
+            paste_repeat: 1,
+            pattern_index: 0,
+            pattern_edit: None,
+            step_entry: false,
+            step_chord: Vec::new(),
+            held_pitches: Vec::new(),
+            recorder: None,
+            record_quantize: false,
+            clock_sync: ClockSync::Internal,
+            keys_mode: false,
+            keys_octave: 4,
+            keys_held: Vec::new(),
+            metronome: Metronome::new(),
+            return_on_stop: false,
+            drawn_cursor: None,
         }
     }
 
@@ -91,6 +188,19 @@ impl AppState {
             let _ = audio_player(&player, player_tx.clone());
         }));
 
+        // MIDI notes arrive as input events, like key presses
+        match midi_input::spawn(self.input_tx.clone()) {
+            Ok(source) => self.event_log.push(format!("MIDI input: {}", source)),
+            Err(e) => self.event_log.push(format!("MIDI input unavailable: {}", e)),
+        }
+        match midi_output::spawn() {
+            Ok((midi_out, destination)) => {
+                self.player.lock().unwrap().set_midi_out(midi_out);
+                self.event_log.push(format!("MIDI output: {}", destination));
+            }
+            Err(e) => self.event_log.push(format!("MIDI output unavailable: {}", e)),
+        }
+
         // Main loop
         self.draw()?;
         self.event_loop()?;
@@ -103,6 +213,16 @@ impl AppState {
         loop {
             match self.input_rx.recv() {
                 Ok(msg) => {
+                    // Clock arrives 24 times a beat, too often to log or redraw for
+                    if matches!(msg, InputEvent::MidiClock) {
+                        if self.clock_sync == ClockSync::External {
+                            self.player.lock().unwrap().external_clock();
+                        }
+                        continue;
+                    }
+                    if !matches!(msg, InputEvent::PlayerBeatChange(_)) {
+                        self.event_log.push(format!("{:?}", msg));
+                    }
                     match msg {
                         InputEvent::Quit => break,
                         
@@ -113,21 +233,34 @@ impl AppState {
                         InputEvent::ViewerOctaveDecrease => {
                             self.score_viewport = self.score_viewport.prev_octave();
                         }
+                        InputEvent::ViewerSemitoneIncrease => {
+                            self.score_viewport = self.score_viewport.next_semitone();
+                        }
+                        InputEvent::ViewerSemitoneDecrease => {
+                            self.score_viewport = self.score_viewport.prev_semitone();
+                        }
+                        InputEvent::ViewerFitToContent => {
+                            let used_pitches = self.score.lock().unwrap().used_pitches();
+                            self.score_viewport = self.score_viewport.fit_to_pitches(&used_pitches);
+                        }
+                        InputEvent::ViewerToggleFold => {
+                            self.score_viewport = self.score_viewport.toggle_fold();
+                        }
                         InputEvent::ViewerBarNext => {
                             let current_time = self.player.lock().unwrap().current_time_b32();
-                            let next_time = current_time + 32 - current_time % 32;
+                            let next_time = current_time + BAR_B32 - current_time % BAR_B32;
                             self.player.lock().unwrap().set_time_b32(next_time);
                             self.score_viewport = self.score_viewport.set_playback_time(next_time);
                             self.score_viewport = self.score_viewport.next_bar(&self.viewport_draw_result.unwrap());
                         }
                         InputEvent::ViewerBarPrevious => {
                             let current_time = self.player.lock().unwrap().current_time_b32();
-                            let prev_time = if current_time < 32 {
+                            let prev_time = if current_time < BAR_B32 {
                                 0
-                            } else if current_time % 32 == 0 {
-                                current_time - 32
+                            } else if current_time % BAR_B32 == 0 {
+                                current_time - BAR_B32
                             } else {
-                                current_time - (current_time % 32)
+                                current_time - (current_time % BAR_B32)
                             };
                             self.player.lock().unwrap().set_time_b32(prev_time);
                             self.score_viewport = self.score_viewport.set_playback_time(prev_time);
@@ -144,30 +277,115 @@ impl AppState {
                             self.cursor = self.cursor.resolution_align(self.score_viewport.resolution.duration_b32());
                         }
                         
+                        // Display zoom, independent from the edit grid
+                        InputEvent::ViewerZoomIn => {
+                            self.score_viewport = self.score_viewport.zoom_in();
+                        }
+                        InputEvent::ViewerZoomOut => {
+                            self.score_viewport = self.score_viewport.zoom_out();
+                        }
+
                         // Playback controls
                         InputEvent::PlayerTogglePlayback => {
                             let mut player_guard = self.player.lock().unwrap();
                             player_guard.toggle_playback();
                         }
+                        InputEvent::PlayFromCursor => self.play_from(self.cursor.time_point(), None),
+                        InputEvent::PlayFromViewport => self.play_from(self.score_viewport.time_point, None),
+                        InputEvent::PlaySelection => match self.selection_range() {
+                            Some(selection_range) => self.play_from(
+                                selection_range.time_point_start_b32,
+                                Some(selection_range.time_point_end_b32),
+                            ),
+                            None => self.event_log.push("Nothing selected to play".to_string()),
+                        },
+                        InputEvent::ReturnOnStopToggle => {
+                            self.return_on_stop = !self.return_on_stop;
+                            self.player.lock().unwrap().set_return_on_stop(self.return_on_stop);
+                        }
+                        InputEvent::FollowModeNext => {
+                            self.score_viewport = self.score_viewport.next_follow();
+                        }
                         InputEvent::PlayerBeatChange(playback_time_point_b32) => {
                             self.score_viewport = self.score_viewport.set_playback_time(playback_time_point_b32);
+                            // Each loop pass while recording is its own undo step
+                            let cut_notes = self.recorder.as_mut().and_then(|recorder| recorder.advance(playback_time_point_b32));
+                            if let Some(cut_notes) = cut_notes {
+                                self.write_recorded_notes(cut_notes);
+                                self.record_undo();
+                            }
                         }
                         
+                        // Grabbed notes follow the arrow keys; the cursor moves along
+                        InputEvent::CursorUp if self.grab.is_some() => {
+                            self.grab = self.grab.as_ref().map(|grab| grab.shift_pitch(1));
+                            self.cursor = self.cursor.up();
+                            self.scroll_to_cursor_pitch();
+                        }
+                        InputEvent::CursorDown if self.grab.is_some() => {
+                            self.grab = self.grab.as_ref().map(|grab| grab.shift_pitch(-1));
+                            self.cursor = self.cursor.down();
+                            self.scroll_to_cursor_pitch();
+                        }
+                        InputEvent::CursorLeft if self.grab.is_some() => {
+                            let step = self.score_viewport.resolution.duration_b32();
+                            self.grab = self.grab.as_ref().map(|grab| grab.shift_time(-(step as i64)));
+                            self.cursor = self.cursor.left(step);
+                        }
+                        InputEvent::CursorRight if self.grab.is_some() => {
+                            let step = self.score_viewport.resolution.duration_b32();
+                            self.grab = self.grab.as_ref().map(|grab| grab.shift_time(step as i64));
+                            self.cursor = self.cursor.right(step);
+                        }
+                        // The playhead would sound the score without the grabbed notes
+                        InputEvent::Scrub(_) if self.grab.is_some() => {
+                            self.event_log.push("Drop the grabbed notes first".to_string());
+                        }
+                        // Each press or auto-repeat of the held key drags the playhead a grid step
+                        InputEvent::Scrub(steps) => {
+                            let step = self.score_viewport.resolution.duration_b32() as i64;
+                            let current_time = self.player.lock().unwrap().current_time_b32();
+                            self.scrub_to(current_time.saturating_add_signed(steps * step));
+                        }
+
                         // Cursor movement
                         InputEvent::CursorUp => {
-                            self.cursor = self.cursor.up();
-                            match self.score_viewport.middle_pitch.next() {
-                                Some(next_pitch) => self.score_viewport.middle_pitch = next_pitch,
-                                None => (),
-                            }
+                            self.cursor = if self.score_viewport.folded {
+                                // Step between the visible rows rather than semitones.
+                                let used_pitches = self.score.lock().unwrap().used_pitches();
+                                match used_pitches.iter().find(|pitch| **pitch > self.cursor.pitch()) {
+                                    Some(pitch) => self.cursor.set_pitch(*pitch),
+                                    None => self.cursor,
+                                }
+                            } else if self.scale_snap {
+                                let key = self.score.lock().unwrap().key;
+                                match key.next_in_key(self.cursor.pitch()) {
+                                    Some(pitch) => self.cursor.set_pitch(pitch),
+                                    None => self.cursor,
+                                }
+                            } else {
+                                self.cursor.up()
+                            };
+                            self.scroll_to_cursor_pitch();
                             self.player.lock().unwrap().preview_note(self.cursor.pitch());
                         }
                         InputEvent::CursorDown => {
-                            self.cursor = self.cursor.down();
-                            match self.score_viewport.middle_pitch.prev() {
-                                Some(prev_pitch) => self.score_viewport.middle_pitch = prev_pitch,
-                                None => (),
-                            }
+                            self.cursor = if self.score_viewport.folded {
+                                let used_pitches = self.score.lock().unwrap().used_pitches();
+                                match used_pitches.iter().rev().find(|pitch| **pitch < self.cursor.pitch()) {
+                                    Some(pitch) => self.cursor.set_pitch(*pitch),
+                                    None => self.cursor,
+                                }
+                            } else if self.scale_snap {
+                                let key = self.score.lock().unwrap().key;
+                                match key.prev_in_key(self.cursor.pitch()) {
+                                    Some(pitch) => self.cursor.set_pitch(pitch),
+                                    None => self.cursor,
+                                }
+                            } else {
+                                self.cursor.down()
+                            };
+                            self.scroll_to_cursor_pitch();
                             self.player.lock().unwrap().preview_note(self.cursor.pitch());
                         }
                         InputEvent::CursorLeft => {
@@ -178,9 +396,49 @@ impl AppState {
                             self.cursor = self.cursor.right(self.score_viewport.resolution.duration_b32());
                             self.selection_buffer = self.selection_buffer.translate_to(self.cursor.time_point());
                         }
-                        
+
+                        // The grabbed notes are out of the score until they are dropped, so
+                        // edits, saving and undo points wait until then
+                        InputEvent::InsertNote
+                        | InputEvent::Yank
+                        | InputEvent::Cut
+                        | InputEvent::Paste
+                        | InputEvent::PasteText(_)
+                        | InputEvent::CopyToSystemClipboard
+                        | InputEvent::Delete
+                        | InputEvent::Transform(_)
+                        | InputEvent::Quantize
+                        | InputEvent::Humanize
+                        | InputEvent::InsertBar
+                        | InputEvent::DeleteBar
+                        | InputEvent::PatternFromSelection
+                        | InputEvent::PatternEditToggle
+                        | InputEvent::ClipPlace
+                        | InputEvent::ClipRepeat(_)
+                        | InputEvent::ClipTranspose(_)
+                        | InputEvent::ClipDelete
+                        | InputEvent::ArrangementFlatten
+                        | InputEvent::GrooveSwingNext
+                        | InputEvent::GrooveSwingUnitToggle
+                        | InputEvent::GrooveTemplateNext
+                        | InputEvent::GrooveTrackSwingNext
+                        | InputEvent::MarkerSet
+                        | InputEvent::MarkerDelete
+                        | InputEvent::MarkerRename(_)
+                        | InputEvent::SongEndAtCursor
+                        | InputEvent::RecordToggle
+                        | InputEvent::StepEntryToggle
+                        | InputEvent::SaveSong
+                        | InputEvent::ExportMidi
+                        | InputEvent::Redo
+                            if self.grab.is_some() =>
+                        {
+                            self.event_log.push("Drop the grabbed notes first".to_string());
+                        }
+
                         // Note editing
                         InputEvent::InsertNote => {
+                            self.record_undo();
                             match self.cursor.mode() {
                                 CursorMode::Select(start, end) => {
                                     // Insert notes for the entire selection
@@ -208,13 +466,20 @@ impl AppState {
                         }
                         // Selection and clipboard
                         InputEvent::Cancel => {
+                            // Grabbing took an undo snapshot with the notes in place, go back to it
+                            if self.grab.take().is_some() {
+                                if let Some(snapshot) = self.history.take_back() {
+                                    self.restore(snapshot);
+                                }
+                            }
                             self.cursor = self.cursor.cancel();
                             self.selection_buffer = SelectionBuffer::None;
                         }
                         InputEvent::Yank => {
                             if let CursorMode::Select(_, _) = self.cursor.mode() {
-                                let selection_range = self.cursor.selection_range().unwrap();
+                                let selection_range = self.selection_range().unwrap();
                                 let selection_score = self.score.lock().unwrap().clone_at_selection(selection_range);
+                                self.registers.store(self.pending_register.take(), selection_score.clone());
                                 self.cursor = self.cursor.yank().right(self.score_viewport.resolution.duration_b32());
                                 self.selection_buffer = SelectionBuffer::Score(
                                     selection_score.translate(Some(self.cursor.time_point())),
@@ -223,9 +488,11 @@ impl AppState {
                         }
                         InputEvent::Cut => {
                             if let CursorMode::Select(_, _) = self.cursor.mode() {
-                                let selection_range = self.cursor.selection_range().unwrap();
+                                self.record_undo();
+                                let selection_range = self.selection_range().unwrap();
                                 let selection_score = self.score.lock().unwrap().clone_at_selection(selection_range);
                                 self.score.lock().unwrap().delete_in_selection(selection_range);
+                                self.registers.store(self.pending_register.take(), selection_score.clone());
                                 self.cursor = self.cursor.end_select();
                                 self.selection_buffer = SelectionBuffer::Score(
                                     selection_score.translate(Some(self.cursor.time_point())),
@@ -233,43 +500,558 @@ impl AppState {
                             }
                         }
                         InputEvent::Paste => {
-                            if let SelectionBuffer::Score(ref selection_buffer_score) = self.selection_buffer {
+                            self.pending_register = None;
+                            if let SelectionBuffer::Score(selection_buffer_score) = self.selection_buffer.clone() {
+                                self.record_undo();
                                 let mut score_guard = self.score.lock().unwrap();
-                                *score_guard = score_guard.merge_down(selection_buffer_score);
-                                let duration = selection_buffer_score.duration();
+                                *score_guard = score_guard.paste(&selection_buffer_score, self.paste_mode, self.paste_repeat);
+                                let duration = selection_buffer_score.duration() * self.paste_repeat;
                                 self.cursor = self.cursor.right(duration);
                                 self.selection_buffer = SelectionBuffer::Score(
                                     selection_buffer_score.translate(Some(self.cursor.time_point())),
                                 );
                             }
                         }
+                        // Ripple edits shift everything after the cursor's bar. The score has one
+                        // tempo and one track, so notes and loop markers are all that move.
+                        // In pattern edit the loop stays put, its times are the song's.
+                        InputEvent::InsertBar | InputEvent::DeleteBar if self.pattern_edit.is_some() => {
+                            self.record_undo();
+                            let bar_start = self.cursor.time_point() - self.cursor.time_point() % BAR_B32;
+                            let mut score_guard = self.score.lock().unwrap();
+                            if matches!(msg, InputEvent::InsertBar) {
+                                score_guard.insert_time(bar_start, BAR_B32);
+                            } else {
+                                score_guard.delete_time(bar_start, BAR_B32);
+                            }
+                        }
+                        InputEvent::InsertBar => {
+                            self.record_undo_with_loop();
+                            let bar_start = self.cursor.time_point() - self.cursor.time_point() % BAR_B32;
+                            self.score.lock().unwrap().insert_time(bar_start, BAR_B32);
+                            self.set_loop_state(self.loop_state.insert_time(bar_start, BAR_B32));
+                        }
+                        InputEvent::DeleteBar => {
+                            self.record_undo_with_loop();
+                            let bar_start = self.cursor.time_point() - self.cursor.time_point() % BAR_B32;
+                            self.score.lock().unwrap().delete_time(bar_start, BAR_B32);
+                            self.set_loop_state(self.loop_state.delete_time(bar_start, BAR_B32));
+                        }
+                        // Patterns and clips. Clip commands act on the clip under the cursor.
+                        InputEvent::PatternFromSelection
+                        | InputEvent::PatternNext
+                        | InputEvent::ClipPlace
+                        | InputEvent::ClipRepeat(_)
+                        | InputEvent::ClipTranspose(_)
+                        | InputEvent::ClipDelete
+                        | InputEvent::ArrangementFlatten
+                            if self.pattern_edit.is_some() =>
+                        {
+                            self.event_log.push("Leave pattern edit to change the arrangement".to_string());
+                        }
+                        InputEvent::PatternFromSelection => {
+                            if let Some(selection_range) = self.selection_range() {
+                                self.record_undo();
+                                let name = self.score.lock().unwrap().make_pattern(selection_range);
+                                if let Some(name) = name {
+                                    self.select_pattern(&name);
+                                    self.event_log.push(format!("Created pattern {}", name));
+                                }
+                                self.cursor = self.cursor.end_select();
+                            }
+                        }
+                        InputEvent::PatternNext => {
+                            let pattern_count = self.score.lock().unwrap().arrangement.patterns.len();
+                            if pattern_count > 0 {
+                                self.pattern_index = (self.pattern_index + 1) % pattern_count;
+                            }
+                        }
+                        InputEvent::ClipPlace => {
+                            if let Some(name) = self.current_pattern_name() {
+                                let bar_start = self.cursor.time_point() - self.cursor.time_point() % BAR_B32;
+                                self.update_arrangement(|arrangement| arrangement.with_clip(Clip::new(&name, bar_start)));
+                            }
+                        }
+                        InputEvent::ClipRepeat(delta) => {
+                            let time_point = self.cursor.time_point();
+                            self.update_arrangement(|arrangement| {
+                                arrangement.update_clip_at(time_point, |clip| clip.change_repeat(delta))
+                            });
+                        }
+                        InputEvent::ClipTranspose(semitones) => {
+                            let time_point = self.cursor.time_point();
+                            self.update_arrangement(|arrangement| {
+                                arrangement.update_clip_at(time_point, |clip| clip.change_transpose(semitones))
+                            });
+                        }
+                        InputEvent::ClipDelete => {
+                            let time_point = self.cursor.time_point();
+                            self.update_arrangement(|arrangement| arrangement.without_clip_at(time_point));
+                        }
+                        InputEvent::ArrangementFlatten => {
+                            self.record_undo();
+                            self.score.lock().unwrap().flatten();
+                            self.event_log.push("Flattened clips into notes".to_string());
+                        }
+                        InputEvent::PatternEditToggle => match self.pattern_edit.take() {
+                            Some(pattern_edit) => self.finish_pattern_edit(pattern_edit),
+                            None => self.start_pattern_edit(),
+                        },
+                        InputEvent::MidiNoteOn(pitch, velocity) => self.note_on(pitch, velocity),
+                        InputEvent::MidiNoteOff(pitch) => self.note_off(pitch),
+                        InputEvent::KeysModeToggle => {
+                            self.keys_mode = !self.keys_mode;
+                            if self.keys_mode {
+                                self.event_log.push("Keys mode: letters play notes, '/' leaves it".to_string());
+                            }
+                        }
+                        InputEvent::KeysNoteOn(semitone) => {
+                            if let Some(pitch) = Pitch::from_index(self.keys_octave * 12 + semitone as u16) {
+                                // Remember the pitch, the octave may change before the key is up
+                                self.keys_held.push((semitone, pitch));
+                                self.note_on(pitch, DEFAULT_VELOCITY);
+                            }
+                        }
+                        InputEvent::KeysNoteOff(semitone) => {
+                            if let Some(index) = self.keys_held.iter().position(|(held, _)| *held == semitone) {
+                                let (_, pitch) = self.keys_held.remove(index);
+                                self.note_off(pitch);
+                            }
+                        }
+                        InputEvent::KeysOctaveUp => {
+                            self.keys_octave = (self.keys_octave + 1).min(OCTAVE_MAX);
+                        }
+                        InputEvent::KeysOctaveDown => {
+                            self.keys_octave = self.keys_octave.saturating_sub(1);
+                        }
+                        // Recording overdubs onto the score from the playback position
+                        InputEvent::RecordToggle => match self.recorder.take() {
+                            Some(mut recorder) => {
+                                let mut player_guard = self.player.lock().unwrap();
+                                let notes = recorder.finish(player_guard.current_time_b32());
+                                player_guard.set_recording(false);
+                                drop(player_guard);
+                                self.write_recorded_notes(notes);
+                            }
+                            None => {
+                                self.record_undo();
+                                let mut player_guard = self.player.lock().unwrap();
+                                self.recorder = Some(Recorder::new(player_guard.current_time_b32()));
+                                player_guard.set_recording(true);
+                                player_guard.play();
+                            }
+                        },
+                        InputEvent::MidiClock => (),
+                        // Transport from the clock source is only followed when synced to it
+                        InputEvent::MidiStart | InputEvent::MidiContinue | InputEvent::MidiStop
+                            if self.clock_sync != ClockSync::External => {}
+                        InputEvent::MidiStart => self.player.lock().unwrap().external_start(),
+                        InputEvent::MidiContinue => self.player.lock().unwrap().play(),
+                        InputEvent::MidiStop => self.player.lock().unwrap().pause(),
+                        InputEvent::ClockSyncNext => {
+                            self.clock_sync = self.clock_sync.next();
+                            self.player.lock().unwrap().set_clock_sync(self.clock_sync);
+                        }
+                        InputEvent::RecordQuantizeToggle => {
+                            self.record_quantize = !self.record_quantize;
+                        }
+                        InputEvent::StepEntryToggle => {
+                            self.step_entry = !self.step_entry;
+                            self.step_chord.clear();
+                            self.held_pitches.clear();
+                        }
+                        InputEvent::PasteModeNext => {
+                            self.paste_mode = self.paste_mode.next();
+                        }
+                        InputEvent::PasteRepeatNext => {
+                            self.paste_repeat = next_paste_repeat(self.paste_repeat);
+                        }
                         InputEvent::Delete => {
-                            if let Some(selection_range) = self.cursor.selection_range() {
+                            if let Some(selection_range) = self.selection_or_note_under_cursor() {
+                                self.record_undo();
                                 self.score.lock().unwrap().delete_in_selection(selection_range);
                                 self.cursor = self.cursor.end_select();
                             }
                         }
+                        // Registers: '"x' loads register x for pasting and names it for the next yank or cut
+                        InputEvent::SelectRegister(name) => {
+                            if Registers::is_register_name(name) {
+                                self.pending_register = Some(name);
+                                if let Some(register_score) = self.registers.get(name) {
+                                    self.selection_buffer = SelectionBuffer::Score(
+                                        register_score.translate(Some(self.cursor.time_point())),
+                                    );
+                                }
+                            }
+                        }
+                        InputEvent::ClipboardHistoryNext => {
+                            if let Some(history_score) = self.registers.cycle_history() {
+                                self.selection_buffer = SelectionBuffer::Score(
+                                    history_score.translate(Some(self.cursor.time_point())),
+                                );
+                                let (position, len) = self.registers.history_position();
+                                self.event_log.push(format!("Clipboard history {} of {}", position, len));
+                            }
+                        }
+                        InputEvent::CopyToSystemClipboard => {
+                            let clip = match self.selection_range() {
+                                Some(selection_range) => Some(self.score.lock().unwrap().clone_at_selection(selection_range)),
+                                None => match &self.selection_buffer {
+                                    SelectionBuffer::Score(selection_buffer_score) => Some(selection_buffer_score.clone()),
+                                    SelectionBuffer::None => None,
+                                },
+                            };
+                            if let Some(clip) = clip {
+                                let text = SongFile::notes_to_text(&clip);
+                                let mut stdout = io::stdout();
+                                stdout.write_all(osc52::copy_sequence(&text).as_bytes())?;
+                                stdout.flush()?;
+                                self.event_log.push(format!("Copied {} lines to the system clipboard", text.lines().count()));
+                            }
+                        }
+                        InputEvent::PasteText(text) => match SongFile::notes_from_text(&text) {
+                            Ok(pasted_score) if !pasted_score.notes.is_empty() => {
+                                self.registers.store(None, pasted_score.clone());
+                                self.selection_buffer = SelectionBuffer::Score(
+                                    pasted_score.translate(Some(self.cursor.time_point())),
+                                );
+                            }
+                            Ok(_) => self.event_log.push("Pasted text holds no notes".to_string()),
+                            Err(e) => self.event_log.push(format!("Failed to read pasted notes: {}", e)),
+                        },
+                        InputEvent::SelectNote => {
+                            let note = self.note_under_cursor();
+                            if let Some(note) = note {
+                                self.cursor = self
+                                    .cursor
+                                    .set_time_point(note.onset_b32 + note.duration_b32)
+                                    .select_from(note.pitch, note.onset_b32);
+                            }
+                        }
+                        InputEvent::SelectAll => {
+                            let full_range = self.score.lock().unwrap().full_range();
+                            if let Some(full_range) = full_range {
+                                self.cursor = self
+                                    .cursor
+                                    .set_time_point(full_range.time_point_end_b32)
+                                    .set_pitch(full_range.pitch_high)
+                                    .select_from(full_range.pitch_low, full_range.time_point_start_b32);
+                            }
+                        }
+                        InputEvent::SelectPitch => {
+                            let end_time_b32 = self.score.lock().unwrap().end_time_b32();
+                            if end_time_b32 > 0 {
+                                let pitch = self.cursor.pitch();
+                                self.cursor = self.cursor.set_time_point(end_time_b32).select_from(pitch, 0);
+                            }
+                        }
+                        InputEvent::ToggleSelectionMode => {
+                            self.selection_mode = self.selection_mode.toggle();
+                        }
+                        InputEvent::Transform(transform) => {
+                            if let Some(selection_range) = self.selection_range() {
+                                self.record_undo();
+                                self.score.lock().unwrap().transform_selection(selection_range, transform);
+                                if let Transform::Transpose(semitones) = transform {
+                                    self.cursor = self.cursor.transpose(semitones);
+                                }
+                            }
+                        }
+                        // Quantize and humanize apply to the selection, or to the whole score
+                        // outside Select mode. Quantize uses the current edit grid.
+                        InputEvent::Quantize => {
+                            let quantize = self.quantize.with_resolution(self.score_viewport.resolution);
+                            self.transform_selection_or_score(Transform::Quantize(quantize));
+                            self.event_log.push(format!("Quantized {}", quantize));
+                        }
+                        InputEvent::QuantizeTargetNext => {
+                            self.quantize = self.quantize.next_target();
+                            self.event_log.push(format!("Quantize {}", self.quantize.target.as_str()));
+                        }
+                        InputEvent::QuantizeStrengthNext => {
+                            self.quantize = self.quantize.next_strength();
+                            self.event_log.push(format!("Quantize strength {}%", self.quantize.strength_percent));
+                        }
+                        InputEvent::QuantizeSwingNext => {
+                            self.quantize = self.quantize.next_swing();
+                            self.event_log.push(format!("Quantize swing {}%", self.quantize.swing_percent));
+                        }
+                        InputEvent::Humanize => {
+                            self.transform_selection_or_score(Transform::Humanize(self.humanize));
+                            self.event_log.push(format!("Humanized {}", self.humanize));
+                            self.humanize = self.humanize.next_seed();
+                        }
+                        // The key and groove are the song's. Pattern edit works on a copy of the
+                        // score that is dropped when it ends, so they wait until then.
+                        InputEvent::GrooveSwingNext
+                        | InputEvent::GrooveSwingUnitToggle
+                        | InputEvent::GrooveTemplateNext
+                        | InputEvent::GrooveTrackSwingNext
+                        | InputEvent::KeyTonicNext
+                        | InputEvent::KeyScaleNext
+                        | InputEvent::KeyToggleCursorTone
+                            if self.pattern_edit.is_some() =>
+                        {
+                            self.event_log.push("Leave pattern edit to change the key or groove".to_string());
+                        }
+                        // Groove only changes playback timing, the notes stay on the grid
+                        InputEvent::GrooveSwingNext => {
+                            self.record_undo();
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.groove = score_guard.groove.next_swing();
+                        }
+                        InputEvent::GrooveSwingUnitToggle => {
+                            self.record_undo();
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.groove = score_guard.groove.toggle_swing_unit();
+                        }
+                        InputEvent::GrooveTemplateNext => {
+                            self.record_undo();
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.groove = score_guard.groove.next_template();
+                        }
+                        InputEvent::GrooveTrackSwingNext => {
+                            self.record_undo();
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.groove = score_guard.groove.next_track_swing(self.mixer.selected_track);
+                        }
+                        InputEvent::GrabToggle => match self.grab.take() {
+                            // Dropping merges with overlapping notes like any insert
+                            Some(grab) => {
+                                let mut score_guard = self.score.lock().unwrap();
+                                for note in grab.moved_notes() {
+                                    score_guard.insert_note(note);
+                                }
+                            }
+                            // Recorded and step entered notes would land in a score without them
+                            None if self.recorder.is_some() || self.step_entry => {
+                                self.event_log.push("Stop recording or step entry to grab notes".to_string());
+                            }
+                            None => {
+                                if let Some(selection_range) = self.selection_or_note_under_cursor() {
+                                    self.record_undo();
+                                    let notes = self.score.lock().unwrap().take_selection(selection_range);
+                                    if !notes.is_empty() {
+                                        self.grab = Some(Grab::new(notes));
+                                    }
+                                    self.cursor = self.cursor.end_select();
+                                }
+                            }
+                        },
+                        InputEvent::GrabHandleNext => {
+                            self.grab = self.grab.as_ref().map(Grab::next_handle);
+                        }
+                        InputEvent::Undo => {
+                            // The snapshot taken when grabbing still holds the grabbed notes
+                            self.grab = None;
+                            let snapshot = self.history.undo(&self.score.lock().unwrap(), self.loop_state);
+                            if let Some(snapshot) = snapshot {
+                                self.restore(snapshot);
+                            }
+                        }
+                        InputEvent::Redo => {
+                            let snapshot = self.history.redo(&self.score.lock().unwrap(), self.loop_state);
+                            if let Some(snapshot) = snapshot {
+                                self.restore(snapshot);
+                            }
+                        }
                         
                         // Loop controls
                         InputEvent::ToggleLoopMode => {
-                            self.loop_state = self.loop_state.toggle_mode();
-                            self.player.lock().unwrap().set_loop_state(self.loop_state);
+                            self.set_loop_state(self.loop_state.toggle_mode());
                         }
                         InputEvent::SetLoopTimes => {
-                            self.loop_state = self.loop_state.mark(self.score_viewport.playback_time_point);
-                            self.player.lock().unwrap().set_loop_state(self.loop_state);
+                            self.set_loop_state(self.loop_state.mark(self.score_viewport.playback_time_point));
+                        }
+                        // The selection becomes the loop, otherwise the cursor marks a loop point
+                        InputEvent::LoopFromCursor => match self.selection_range() {
+                            Some(selection_range) => self.set_loop_state(
+                                self.loop_state
+                                    .set_region(selection_range.time_point_start_b32, selection_range.time_point_end_b32),
+                            ),
+                            None => self.set_loop_state(self.loop_state.mark(self.cursor.time_point())),
+                        },
+                        InputEvent::LoopNudge(steps) => {
+                            let step = self.score_viewport.resolution.duration_b32() as i64;
+                            self.set_loop_state(self.loop_state.nudge(steps * step));
+                        }
+                        InputEvent::LoopSnapToBar => self.set_loop_state(self.loop_state.snap_to_bar()),
+                        InputEvent::LoopDouble => self.set_loop_state(self.loop_state.double()),
+                        InputEvent::LoopHalve => self.set_loop_state(self.loop_state.halve()),
+                        InputEvent::LoopSection => {
+                            let section = self.score.lock().unwrap().section_at(self.cursor.time_point());
+                            match section {
+                                Some((start, end)) => self.set_loop_state(
+                                    self.loop_state.set_region(start, end).set_mode(LoopMode::Looping),
+                                ),
+                                None => self.event_log.push("No marker at or before the cursor".to_string()),
+                            }
+                        }
+
+                        // Markers are song-wide, so they wait until pattern edit is over
+                        InputEvent::MarkerSet | InputEvent::MarkerDelete | InputEvent::MarkerRename(_)
+                            if self.pattern_edit.is_some() =>
+                        {
+                            self.event_log.push("Leave pattern edit to change markers".to_string());
+                        }
+                        InputEvent::SongEndAtCursor if self.pattern_edit.is_some() => {
+                            self.event_log.push("Leave pattern edit to change the song length".to_string());
+                        }
+                        // Ends the song after the cursor's bar, or again there goes back to
+                        // following the content
+                        InputEvent::SongEndAtCursor => {
+                            self.record_undo();
+                            let end_b32 = (self.cursor.time_point() / BAR_B32 + 1) * BAR_B32;
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.length_b32 = if score_guard.length_b32 == Some(end_b32) { None } else { Some(end_b32) };
+                            self.event_log.push(format!("Song length: {} bars", score_guard.song_length_b32() / BAR_B32));
+                        }
+                        // A new marker takes the section name after the previous marker's,
+                        // setting it again on the same spot renames it to the next name
+                        InputEvent::MarkerSet => {
+                            self.record_undo();
+                            let time_b32 = self.cursor.time_point();
+                            let mut score_guard = self.score.lock().unwrap();
+                            let name = match score_guard.marker_at(time_b32) {
+                                Some(marker) => marker::next_section_name(&marker.name),
+                                None => marker::next_section_name(
+                                    score_guard.previous_marker(time_b32).map_or("", |marker| marker.name.as_str()),
+                                ),
+                            };
+                            score_guard.set_marker(time_b32, name);
+                        }
+                        InputEvent::MarkerNameEntry => {
+                            self.event_log.push("Type the marker name, Enter to set it, Esc to cancel".to_string());
+                        }
+                        // Names the marker at the cursor, setting one there if there is none
+                        InputEvent::MarkerRename(name) => {
+                            let name = name.trim();
+                            if name.is_empty() {
+                                self.event_log.push("Marker names can't be empty".to_string());
+                            } else {
+                                self.record_undo();
+                                self.score.lock().unwrap().set_marker(self.cursor.time_point(), name);
+                            }
+                        }
+                        InputEvent::MarkerDelete => {
+                            self.record_undo();
+                            self.score.lock().unwrap().remove_marker(self.cursor.time_point());
+                        }
+                        InputEvent::MarkerNext | InputEvent::MarkerPrevious => {
+                            let time_b32 = self.cursor.time_point();
+                            let score_guard = self.score.lock().unwrap();
+                            let marker = if matches!(msg, InputEvent::MarkerNext) {
+                                score_guard.next_marker(time_b32)
+                            } else {
+                                score_guard.previous_marker(time_b32)
+                            };
+                            if let Some(marker_time_b32) = marker.map(|marker| marker.time_b32) {
+                                drop(score_guard);
+                                // The viewport follows the cursor, as for overview jumps
+                                self.cursor = self.cursor.set_time_point(marker_time_b32);
+                                self.score_viewport = self.score_viewport.set_time_point(marker_time_b32);
+                            }
                         }
                         
                         // File operations
+                        InputEvent::SaveSong if self.pattern_edit.is_some() => {
+                            self.event_log.push("Leave pattern edit before saving".to_string());
+                        }
+                        InputEvent::ExportMidi if self.pattern_edit.is_some() => {
+                            self.event_log.push("Leave pattern edit before exporting".to_string());
+                        }
+                        InputEvent::ExportMidi => {
+                            let result = self.song_file.export_midi(&self.score.lock().unwrap());
+                            match result {
+                                Ok(path) => self.event_log.push(format!("Exported {}", path.display())),
+                                Err(e) => self.event_log.push(format!("Failed to export MIDI: {}", e)),
+                            }
+                        }
                         InputEvent::SaveSong => {
-                            if let Err(e) = self.song_file.save(&self.score.lock().unwrap()) {
+                            let score_guard = self.score.lock().unwrap();
+                            if let Err(e) = self.song_file.save(&score_guard, &self.loop_state) {
                                 error!("Failed to save song: {}", e);
+                                self.event_log.push(format!("Failed to save song: {}", e));
                             }
                         }
                         
                         InputEvent::SelectIn => {
                             self.cursor = self.cursor.start_select();
                         }
+
+                        // Display
+                        InputEvent::ToggleTheme => {
+                            self.theme = self.theme.toggle_mode();
+                            // Styles changed everywhere, so repaint the whole screen.
+                            self.buffer = None;
+                        }
+                        InputEvent::CyclePanelView => {
+                            self.panel_view = self.panel_view.next();
+                        }
+
+                        // Key and scale
+                        InputEvent::KeyTonicNext => {
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.key = score_guard.key.next_tonic();
+                        }
+                        InputEvent::KeyScaleNext => {
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.key = score_guard.key.next_scale();
+                        }
+                        InputEvent::KeyToggleCursorTone => {
+                            let mut score_guard = self.score.lock().unwrap();
+                            score_guard.key = score_guard.key.toggle_tone(self.cursor.pitch().tone);
+                        }
+                        InputEvent::ToggleScaleSnap => {
+                            self.scale_snap = !self.scale_snap;
+                        }
+
+                        // Overview, only while its panel is shown
+                        InputEvent::OverviewBarPrevious | InputEvent::OverviewBarNext | InputEvent::OverviewJump
+                            if self.panel_view != PanelView::Overview => {}
+                        InputEvent::OverviewBarPrevious => {
+                            self.overview_bar = self.overview_bar.saturating_sub(1);
+                        }
+                        InputEvent::OverviewBarNext => {
+                            let bar_count = self.score.lock().unwrap().onsets_per_bar().len();
+                            self.overview_bar = overview_component::clamp_bar(self.overview_bar + 1, bar_count);
+                        }
+                        InputEvent::OverviewJump => {
+                            // The viewport follows the cursor, so move both to the bar.
+                            let bar_time = self.overview_bar as u64 * BAR_B32;
+                            self.cursor = self.cursor.set_time_point(bar_time);
+                            self.score_viewport = self.score_viewport.set_time_point(bar_time);
+                        }
+
+                        // Mixer
+                        InputEvent::MixerVolumeUp => {
+                            self.mixer = self.mixer.volume_up();
+                            self.player.lock().unwrap().set_mixer(self.mixer.clone());
+                        }
+                        InputEvent::MixerVolumeDown => {
+                            self.mixer = self.mixer.volume_down();
+                            self.player.lock().unwrap().set_mixer(self.mixer.clone());
+                        }
+                        InputEvent::MixerToggleMute => {
+                            self.mixer = self.mixer.toggle_mute();
+                            self.player.lock().unwrap().set_mixer(self.mixer.clone());
+                        }
+                        InputEvent::MixerMidiChannelNext => {
+                            self.mixer = self.mixer.next_midi_channel();
+                            self.player.lock().unwrap().set_mixer(self.mixer.clone());
+                        }
+                        InputEvent::MetronomeModeNext => {
+                            self.metronome = self.metronome.next_mode();
+                            self.player.lock().unwrap().set_metronome(self.metronome);
+                        }
+                        InputEvent::MetronomeVolumeNext => {
+                            self.metronome = self.metronome.next_volume();
+                            self.player.lock().unwrap().set_metronome(self.metronome);
+                        }
+                        InputEvent::CountInNext => {
+                            self.metronome = self.metronome.next_count_in();
+                            self.player.lock().unwrap().set_metronome(self.metronome);
+                        }
                     }
                     self.draw()?;
                 }
@@ -282,38 +1064,321 @@ impl AppState {
         Ok(())
     }
 
+    fn record_undo(&mut self) {
+        self.history.record(&self.score.lock().unwrap(), None);
+    }
+
+    // For ripple edits, which shift the loop as well as the notes
+    fn record_undo_with_loop(&mut self) {
+        self.history.record(&self.score.lock().unwrap(), Some(self.loop_state));
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        *self.score.lock().unwrap() = snapshot.score;
+        if let Some(loop_state) = snapshot.loop_state {
+            self.set_loop_state(loop_state);
+        }
+    }
+
+    fn play_from(&mut self, start_b32: u64, end_b32: Option<u64>) {
+        self.player.lock().unwrap().play_from(start_b32, end_b32);
+        self.score_viewport = self.score_viewport.set_playback_time(start_b32);
+    }
+
+    fn scrub_to(&mut self, time_b32: u64) {
+        self.player.lock().unwrap().scrub_to(time_b32);
+        self.score_viewport = self.score_viewport.set_playback_time(time_b32);
+    }
+
+    fn set_loop_state(&mut self, loop_state: LoopState) {
+        self.loop_state = loop_state;
+        self.player.lock().unwrap().set_loop_state(loop_state);
+    }
+
+    // The cursor selection, using the current selection mode.
+    fn selection_range(&self) -> Option<SelectionRange> {
+        self.cursor
+            .selection_range()
+            .map(|selection_range| selection_range.with_mode(self.selection_mode))
+    }
+
+    fn note_under_cursor(&self) -> Option<Note> {
+        self.score
+            .lock()
+            .unwrap()
+            .note_at(self.cursor.pitch(), self.cursor.time_point())
+    }
+
+    fn selection_or_note_under_cursor(&self) -> Option<SelectionRange> {
+        self.selection_range()
+            .or_else(|| self.note_under_cursor().map(SelectionRange::for_note))
+    }
+
+    fn transform_selection_or_score(&mut self, transform: Transform) {
+        let selection_range = match self.selection_range() {
+            Some(selection_range) => Some(selection_range),
+            None => self.score.lock().unwrap().full_range(),
+        };
+        if let Some(selection_range) = selection_range {
+            self.record_undo();
+            self.score.lock().unwrap().transform_selection(selection_range, transform);
+        }
+    }
+
+    // Recorded notes are quantized to the edit grid when input quantize is on.
+    fn write_recorded_notes(&mut self, notes: Vec<Note>) {
+        let quantize = self.quantize.with_resolution(self.score_viewport.resolution);
+        let mut score_guard = self.score.lock().unwrap();
+        for note in notes {
+            score_guard.insert_note(if self.record_quantize { quantize.apply(note) } else { note });
+        }
+    }
+
+    // Notes played on a MIDI keyboard or the computer keyboard audition through the player.
+    // In step entry the chord played is written at the cursor once every key is up, then
+    // the cursor moves one step.
+    fn note_on(&mut self, pitch: Pitch, velocity: u8) {
+        self.player.lock().unwrap().live_note_on(pitch, velocity);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.note_on(pitch, velocity, self.player.lock().unwrap().current_time_b32());
+        } else if self.step_entry {
+            self.held_pitches.push(pitch);
+            if !self.step_chord.iter().any(|(chord_pitch, _)| *chord_pitch == pitch) {
+                self.step_chord.push((pitch, velocity));
+            }
+        }
+    }
+
+    fn note_off(&mut self, pitch: Pitch) {
+        self.player.lock().unwrap().live_note_off(pitch);
+        let time_b32 = self.player.lock().unwrap().current_time_b32();
+        let recorded_note = self.recorder.as_mut().and_then(|recorder| recorder.note_off(pitch, time_b32));
+        if let Some(note) = recorded_note {
+            self.write_recorded_notes(vec![note]);
+        }
+        self.held_pitches.retain(|held| *held != pitch);
+        if self.held_pitches.is_empty() && !self.step_chord.is_empty() {
+            self.record_undo();
+            let step = self.score_viewport.resolution.duration_b32();
+            let chord = std::mem::take(&mut self.step_chord);
+            let mut score_guard = self.score.lock().unwrap();
+            for &(pitch, velocity) in &chord {
+                score_guard.insert_note(Note {
+                    pitch,
+                    onset_b32: self.cursor.time_point(),
+                    duration_b32: step,
+                    velocity,
+                });
+            }
+            drop(score_guard);
+            let lowest = chord.iter().map(|(pitch, _)| *pitch).min_by_key(Pitch::index).unwrap();
+            self.cursor = self.cursor.set_pitch(lowest).right(step);
+            self.scroll_to_cursor_pitch();
+        }
+    }
+
+    fn record_label(&self) -> Option<String> {
+        let recorder = self.recorder.as_ref()?;
+        let quantize = if self.record_quantize { " Q" } else { "" };
+        if self.loop_state.is_looping() {
+            Some(format!("REC{} L{}", quantize, recorder.layer()))
+        } else {
+            Some(format!("REC{}", quantize))
+        }
+    }
+
+    // The song's arrangement, also while one of its patterns is being edited.
+    fn arrangement(&self) -> Arrangement {
+        match &self.pattern_edit {
+            Some(pattern_edit) => pattern_edit.song.arrangement.clone(),
+            None => self.score.lock().unwrap().arrangement.clone(),
+        }
+    }
+
+    fn current_pattern_name(&self) -> Option<String> {
+        self.arrangement()
+            .patterns
+            .get(self.pattern_index)
+            .map(|pattern| pattern.name.clone())
+    }
+
+    fn select_pattern(&mut self, name: &str) {
+        if let Some(index) = self.arrangement().patterns.iter().position(|pattern| pattern.name == name) {
+            self.pattern_index = index;
+        }
+    }
+
+    fn update_arrangement(&mut self, update: impl FnOnce(&Arrangement) -> Arrangement) {
+        self.record_undo();
+        let mut score_guard = self.score.lock().unwrap();
+        let arrangement = update(&score_guard.arrangement);
+        score_guard.set_arrangement(arrangement);
+    }
+
+    // Swaps the pattern in for the song, so every editing command works on it. The clip
+    // under the cursor picks the pattern, otherwise the current one.
+    fn start_pattern_edit(&mut self) {
+        let arrangement = self.arrangement();
+        let time_point = self.cursor.time_point();
+        let (pattern, pattern_time_point) = match arrangement.clip_at(time_point) {
+            Some(clip) => {
+                let pattern = arrangement.pattern(&clip.pattern).unwrap();
+                (pattern, (time_point - clip.start_b32) % pattern.length_b32)
+            }
+            None => match arrangement.patterns.get(self.pattern_index) {
+                Some(pattern) => (pattern, 0),
+                None => return,
+            },
+        };
+
+        let mut score_guard = self.score.lock().unwrap();
+        let mut pattern_score = pattern.score.clone();
+        pattern_score.bpm = score_guard.bpm;
+        pattern_score.key = score_guard.key;
+        pattern_score.groove = score_guard.groove.clone();
+        let song = std::mem::replace(&mut *score_guard, pattern_score);
+        drop(score_guard);
+
+        self.pattern_edit = Some(PatternEdit {
+            name: pattern.name.clone(),
+            length_b32: pattern.length_b32,
+            song,
+            history: std::mem::take(&mut self.history),
+            cursor: self.cursor,
+        });
+        self.select_pattern(&pattern.name);
+        self.cursor = self.cursor.cancel().set_time_point(pattern_time_point);
+        self.event_log.push(format!("Editing pattern {}", pattern.name));
+    }
+
+    // Puts the song back with the edited pattern, which updates every clip playing it.
+    // The whole edit is one undo step.
+    fn finish_pattern_edit(&mut self, pattern_edit: PatternEdit) {
+        let mut score_guard = self.score.lock().unwrap();
+        let pattern_score = std::mem::replace(&mut *score_guard, pattern_edit.song);
+        self.history = pattern_edit.history;
+        self.history.record(&score_guard, None);
+
+        let pattern = Pattern {
+            name: pattern_edit.name,
+            length_b32: pattern_edit.length_b32.max(pattern_score.end_time_b32().div_ceil(BAR_B32) * BAR_B32),
+            score: pattern_score,
+        };
+        let arrangement = score_guard.arrangement.with_pattern(pattern);
+        score_guard.set_arrangement(arrangement);
+        self.cursor = pattern_edit.cursor;
+    }
+
+    fn arrangement_label(&self) -> Option<String> {
+        if let Some(pattern_edit) = &self.pattern_edit {
+            return Some(format!("EDIT:{}", pattern_edit.name));
+        }
+        let score = self.score.lock().unwrap();
+        score
+            .arrangement
+            .clip_at(self.cursor.time_point())
+            .map(|clip| format!("CLIP:{} x{} {:+}", clip.pattern, clip.repeat, clip.transpose))
+    }
+
+    fn scroll_to_cursor_pitch(&mut self) {
+        if let Some(viewport_draw_result) = self.viewport_draw_result {
+            self.score_viewport = self
+                .score_viewport
+                .scroll_to_pitch(self.cursor.pitch(), &viewport_draw_result);
+        }
+    }
+
+    fn panel_component(&self) -> Box<dyn DrawComponent> {
+        let component: Box<dyn DrawComponent> = match self.panel_view {
+            PanelView::Mixer => Box::new(MixerComponent::new(
+                self.mixer.clone(),
+                self.score.lock().unwrap().groove.clone(),
+                self.clock_sync,
+                self.metronome,
+                self.theme,
+            )),
+            PanelView::NoteInspector => Box::new(NoteInspectorComponent::new(
+                Arc::clone(&self.score),
+                self.cursor,
+                self.theme,
+            )),
+            PanelView::Overview => Box::new(OverviewComponent::new(
+                Arc::clone(&self.score),
+                self.viewport_draw_result,
+                self.score_viewport.playback_time_point,
+                self.loop_state,
+                self.overview_bar,
+                self.theme,
+            )),
+            PanelView::KeyCheck => {
+                Box::new(KeyCheckComponent::new(Arc::clone(&self.score), self.theme))
+            }
+            PanelView::EventLog => {
+                Box::new(EventLogComponent::new(self.event_log.clone(), self.theme))
+            }
+            PanelView::Arrangement => Box::new(ArrangementComponent::new(
+                self.arrangement(),
+                self.current_pattern_name(),
+                match &self.pattern_edit {
+                    Some(pattern_edit) => pattern_edit.cursor.time_point(),
+                    None => self.cursor.time_point(),
+                },
+                self.theme,
+            )),
+        };
+        Box::new(PanelComponent::new(self.panel_view, component, self.theme))
+    }
+
     fn draw(&mut self) -> io::Result<()> {
         let (width, height) = terminal::size()?;
-        let mut buffer = vec![vec![' '; width as usize]; height as usize];
+        let mut buffer = vec![vec![Cell::default(); width as usize]; height as usize];
 
         let mut stdout = io::stdout();
         if self.buffer.is_none() {
             stdout.execute(terminal::Clear(ClearType::All))?;
         }
 
-        let base_component = Window::new(vec![Box::new(BoxDrawComponent::new(Box::new(
-            VSplitDrawComponent::new(
+        let base_component = Window::new(vec![Box::new(BoxDrawComponent::new(
+            Box::new(VSplitDrawComponent::new(
                 draw_components::VSplitStyle::HalfWithDivider,
+                self.theme.border,
                 Box::new(ScoreDrawComponent::new(
                     Arc::clone(&self.score),
                     self.player.lock().unwrap().state(),
                     self.score_viewport,
                     self.input_tx.clone(),
                     self.cursor,
-                    self.selection_buffer.clone(),
+                    match &self.grab {
+                        Some(grab) => SelectionBuffer::Score(grab.preview_score()),
+                        None => self.selection_buffer.clone(),
+                    },
                     self.loop_state,
+                    self.theme,
                 )),
                 Box::new(VSplitDrawComponent::new(
                     draw_components::VSplitStyle::StatusBarNoDivider,
-                    Box::new(NullComponent {}),
+                    self.theme.border,
+                    self.panel_component(),
                     Box::new(StatusBarComponent::new(
                         self.cursor,
+                        self.grab.as_ref().map(Grab::handle),
+                        self.selection_mode,
+                        self.pending_register,
+                        self.arrangement_label(),
+                        self.step_entry,
+                        self.keys_mode.then(|| format!("KEYS C{} /:exit", self.keys_octave)),
+                        self.record_label(),
+                        self.return_on_stop,
+                        self.paste_mode,
+                        self.paste_repeat,
                         self.score_viewport,
                         self.loop_state,
+                        self.theme,
                     )),
                 )),
-            ),
-        )))]);
+            )),
+            self.theme.border,
+        ))]);
 
         let position = Position {
             x: 0,
@@ -327,18 +1392,22 @@ impl AppState {
                 DrawResult::ViewportDrawResult(viewport_draw_result) => {
                     self.viewport_draw_result = Some(viewport_draw_result);
                     let player = self.player.lock().unwrap();
-                    if player.is_playing()
-                        && (player.current_time_b32() < viewport_draw_result.time_point_start
-                            || player.current_time_b32() >= viewport_draw_result.time_point_end)
-                    {
-                        let new_time = player.current_time_b32() - player.current_time_b32() % 32;
-                        self.score_viewport = self.score_viewport.set_time_point(new_time);
+                    let following = player.is_playing() && self.score_viewport.follow != FollowMode::Off;
+                    if following {
+                        self.score_viewport = self.score_viewport.follow_playhead(player.current_time_b32(), &viewport_draw_result);
                     }
-                    
-                    if self.cursor.time_point() < viewport_draw_result.time_point_start
-                        || self.cursor.time_point() >= viewport_draw_result.time_point_end - 2
+                    drop(player);
+
+                    // While following, the view belongs to the playhead
+                    let drawn_cursor = Some((self.cursor.time_point(), self.score_viewport.zoom));
+                    let cursor_moved = self.drawn_cursor != drawn_cursor;
+                    self.drawn_cursor = drawn_cursor;
+                    if !following
+                        && cursor_moved
+                        && (self.cursor.time_point() < viewport_draw_result.time_point_start
+                            || self.cursor.time_point() >= viewport_draw_result.time_point_end - 2)
                     {
-                        let new_time = self.cursor.time_point() - self.cursor.time_point() % 32;
+                        let new_time = self.cursor.time_point() - self.cursor.time_point() % BAR_B32;
                         self.score_viewport = self.score_viewport.set_time_point(new_time);
                     }
                 }
@@ -347,13 +1416,13 @@ impl AppState {
 
         for y in 0..height {
             for x in 0..width {
-                let char = buffer[y as usize][x as usize];
+                let cell = buffer[y as usize][x as usize];
                 if self.buffer.is_none()
-                    || char != self.buffer.as_ref().unwrap()[y as usize][x as usize]
+                    || cell != self.buffer.as_ref().unwrap()[y as usize][x as usize]
                 {
                     stdout
                         .queue(cursor::MoveTo(x, y))?
-                        .queue(style::Print(char))?;
+                        .queue(style::PrintStyledContent(cell.styled()))?;
                 }
             }
         }
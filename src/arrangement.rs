@@ -0,0 +1,169 @@
+use crate::ripple;
+use crate::score::{Note, Score};
+
+const REPEAT_MAX: u64 = 64;
+const TRANSPOSE_MAX: i32 = 48;
+
+// A named phrase that clips play back. Onsets are relative to the pattern start.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub name: String,
+    pub length_b32: u64,
+    pub score: Score,
+}
+
+// One placement of a pattern on the timeline, played `repeat` times back to back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub pattern: String,
+    pub start_b32: u64,
+    pub repeat: u64,
+    pub transpose: i32,
+}
+
+impl Clip {
+    pub fn new(pattern: &str, start_b32: u64) -> Clip {
+        Clip {
+            pattern: pattern.to_string(),
+            start_b32,
+            repeat: 1,
+            transpose: 0,
+        }
+    }
+
+    pub fn change_repeat(&self, delta: i64) -> Clip {
+        let mut clip = self.clone();
+        clip.repeat = self.repeat.saturating_add_signed(delta).clamp(1, REPEAT_MAX);
+        clip
+    }
+
+    pub fn change_transpose(&self, semitones: i32) -> Clip {
+        let mut clip = self.clone();
+        clip.transpose = (self.transpose + semitones).clamp(-TRANSPOSE_MAX, TRANSPOSE_MAX);
+        clip
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Arrangement {
+    pub patterns: Vec<Pattern>,
+    pub clips: Vec<Clip>,
+}
+
+impl Arrangement {
+    pub fn new() -> Arrangement {
+        Arrangement {
+            patterns: Vec::new(),
+            clips: Vec::new(),
+        }
+    }
+
+    pub fn pattern(&self, name: &str) -> Option<&Pattern> {
+        self.patterns.iter().find(|pattern| pattern.name == name)
+    }
+
+    // First free name of the form "P1", "P2", ...
+    pub fn next_pattern_name(&self) -> String {
+        (1..)
+            .map(|n| format!("P{}", n))
+            .find(|name| self.pattern(name).is_none())
+            .unwrap()
+    }
+
+    // Adds the pattern, or replaces the one with the same name.
+    pub fn with_pattern(&self, pattern: Pattern) -> Arrangement {
+        let mut arrangement = self.clone();
+        match arrangement.patterns.iter_mut().find(|existing| existing.name == pattern.name) {
+            Some(existing) => *existing = pattern,
+            None => arrangement.patterns.push(pattern),
+        }
+        arrangement
+    }
+
+    pub fn with_clip(&self, clip: Clip) -> Arrangement {
+        let mut arrangement = self.clone();
+        arrangement.clips.push(clip);
+        arrangement
+    }
+
+    pub fn without_clips(&self) -> Arrangement {
+        let mut arrangement = self.clone();
+        arrangement.clips.clear();
+        arrangement
+    }
+
+    // Time covered by all repeats of the clip.
+    pub fn clip_span_b32(&self, clip: &Clip) -> u64 {
+        self.pattern(&clip.pattern)
+            .map_or(0, |pattern| pattern.length_b32 * clip.repeat)
+    }
+
+    // The most recently placed clip sounding at `time_point_b32`.
+    pub fn clip_at(&self, time_point_b32: u64) -> Option<&Clip> {
+        self.clip_index_at(time_point_b32).map(|index| &self.clips[index])
+    }
+
+    fn clip_index_at(&self, time_point_b32: u64) -> Option<usize> {
+        self.clips.iter().rposition(|clip| {
+            time_point_b32 >= clip.start_b32 && time_point_b32 < clip.start_b32 + self.clip_span_b32(clip)
+        })
+    }
+
+    // Replaces the clip at `time_point_b32` with `update(clip)`.
+    pub fn update_clip_at(&self, time_point_b32: u64, update: impl Fn(&Clip) -> Clip) -> Arrangement {
+        let mut arrangement = self.clone();
+        if let Some(index) = self.clip_index_at(time_point_b32) {
+            arrangement.clips[index] = update(&self.clips[index]);
+        }
+        arrangement
+    }
+
+    pub fn without_clip_at(&self, time_point_b32: u64) -> Arrangement {
+        let mut arrangement = self.clone();
+        if let Some(index) = self.clip_index_at(time_point_b32) {
+            arrangement.clips.remove(index);
+        }
+        arrangement
+    }
+
+    pub fn insert_time(&self, at_b32: u64, length_b32: u64) -> Arrangement {
+        let mut arrangement = self.clone();
+        for clip in &mut arrangement.clips {
+            clip.start_b32 = ripple::after_insert(clip.start_b32, at_b32, length_b32);
+        }
+        arrangement
+    }
+
+    // Clips starting in the deleted span are removed.
+    pub fn delete_time(&self, start_b32: u64, length_b32: u64) -> Arrangement {
+        let mut arrangement = self.clone();
+        arrangement
+            .clips
+            .retain(|clip| clip.start_b32 < start_b32 || clip.start_b32 >= start_b32 + length_b32);
+        for clip in &mut arrangement.clips {
+            clip.start_b32 = ripple::after_delete(clip.start_b32, start_b32, length_b32);
+        }
+        arrangement
+    }
+
+    // Inserts every clip's notes into `score`. Notes transposed out of range are dropped.
+    pub fn render(&self, score: &mut Score) {
+        for clip in &self.clips {
+            let Some(pattern) = self.pattern(&clip.pattern) else {
+                continue;
+            };
+            for repeat in 0..clip.repeat {
+                let offset_b32 = clip.start_b32 + repeat * pattern.length_b32;
+                for note in pattern.score.notes.values().flatten() {
+                    if let Some(pitch) = note.pitch.transpose(clip.transpose) {
+                        score.insert_note(Note {
+                            pitch,
+                            onset_b32: note.onset_b32 + offset_b32,
+                            ..*note
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 use crossterm::cursor;
 
 use crate::{pitch::Pitch, score::Score};
-use crate::selection_range::SelectionRange;
+use crate::selection_range::{SelectionMode, SelectionRange};
 
 #[derive(Clone, Copy)]
 pub struct Cursor {
@@ -76,6 +76,18 @@ impl Cursor {
         next_cursor
     }
 
+    pub fn set_time_point(self, time_point: u64) -> Cursor {
+        let mut next_cursor = self;
+        next_cursor.time_point = time_point;
+        next_cursor
+    }
+
+    pub fn set_pitch(self, pitch: Pitch) -> Cursor {
+        let mut next_cursor = self;
+        next_cursor.pitch = pitch;
+        next_cursor
+    }
+
     pub fn up(self) -> Cursor {
         let mut next_cursor = self;
         let next_pitch = self.pitch.next();
@@ -94,6 +106,23 @@ impl Cursor {
         next_cursor
     }
 
+    // Moves the cursor and the selection anchor together, so a transposed selection
+    // stays selected. Unchanged if either end would leave the pitch range.
+    pub fn transpose(self, semitones: i32) -> Cursor {
+        let mut next_cursor = self;
+        let Some(pitch) = self.pitch.transpose(semitones) else {
+            return self;
+        };
+        next_cursor.pitch = pitch;
+        if let CursorMode::Select(anchor_pitch, anchor_time_point) = self.mode {
+            let Some(anchor_pitch) = anchor_pitch.transpose(semitones) else {
+                return self;
+            };
+            next_cursor.mode = CursorMode::Select(anchor_pitch, anchor_time_point);
+        }
+        next_cursor
+    }
+
     pub fn show(self) -> Cursor {
         let mut next_cursor = self;
         next_cursor.visibility = Visibility::Visible;
@@ -111,16 +140,17 @@ impl Cursor {
         true
     }
 
-    pub fn visible_at(self, pitch: Pitch, time_point: u64) -> bool {
+    // Whether the cursor covers `pitch` anywhere in the time span [start, end).
+    pub fn visible_between(self, pitch: Pitch, start: u64, end: u64) -> bool {
         if !self.visible() {
             return false;
         }
         match self.mode {
             CursorMode::Move | CursorMode::Yank => {
-                time_point == self.time_point && self.pitch == pitch
+                self.time_point >= start && self.time_point < end && self.pitch == pitch
             }
             CursorMode::Insert(onset_b32) => {
-                time_point >= onset_b32 && time_point <= self.time_point && self.pitch == pitch
+                onset_b32 < end && self.time_point >= start && self.pitch == pitch
             }
             CursorMode::Select(start_pitch, onset_b32) => {
                 let (low_pitch, high_pitch) = if self.pitch > start_pitch {
@@ -128,8 +158,8 @@ impl Cursor {
                 } else {
                     (self.pitch, start_pitch)
                 };
-                time_point >= onset_b32
-                    && time_point <= self.time_point
+                onset_b32 < end
+                    && self.time_point >= start
                     && pitch >= low_pitch
                     && pitch <= high_pitch
             }
@@ -175,6 +205,13 @@ impl Cursor {
         }
     }
 
+    // Selects from the anchor to the current cursor position.
+    pub fn select_from(self, anchor_pitch: Pitch, anchor_time_point: u64) -> Cursor {
+        let mut cursor = self;
+        cursor.mode = CursorMode::Select(anchor_pitch, anchor_time_point);
+        cursor
+    }
+
     pub fn end_select(self) -> Cursor {
         let mut cursor = self;
         cursor.mode = CursorMode::Move;
@@ -210,6 +247,7 @@ impl Cursor {
                 time_point_end_b32,
                 pitch_low,
                 pitch_high,
+                mode: SelectionMode::Onset,
             });
         }
         None
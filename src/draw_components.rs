@@ -1,8 +1,92 @@
 use crate::pitch::Pitch;
+use crossterm::style::{Attribute, Attributes, Color, ContentStyle, StyledContent};
 
+pub mod arrangement_component;
+pub mod event_log_component;
+pub mod key_check_component;
+pub mod mixer_component;
+pub mod note_inspector_component;
+pub mod overview_component;
+pub mod panel_component;
 pub mod score_draw_component;
 pub mod status_bar_component;
 
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Style {
+        Style {
+            fg: Some(color),
+            ..Style::default()
+        }
+    }
+
+    pub fn bg(color: Color) -> Style {
+        Style {
+            bg: Some(color),
+            ..Style::default()
+        }
+    }
+
+    pub fn on(self, color: Color) -> Style {
+        let mut style = self;
+        style.bg = Some(color);
+        style
+    }
+
+    pub fn with(self, attribute: Attribute) -> Style {
+        let mut style = self;
+        style.attributes.set(attribute);
+        style
+    }
+
+    // Layers `other` on top of this style: set colors win, attributes accumulate.
+    pub fn overlay(self, other: Style) -> Style {
+        let mut style = self;
+        if other.fg.is_some() {
+            style.fg = other.fg;
+        }
+        if other.bg.is_some() {
+            style.bg = other.bg;
+        }
+        style.attributes.extend(other.attributes);
+        style
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cell {
+    pub value: char,
+    pub style: Style,
+}
+
+impl Cell {
+    pub fn new(value: char, style: Style) -> Cell {
+        Cell { value, style }
+    }
+
+    pub fn styled(&self) -> StyledContent<char> {
+        let content_style = ContentStyle {
+            foreground_color: self.style.fg,
+            background_color: self.style.bg,
+            underline_color: None,
+            attributes: self.style.attributes,
+        };
+        StyledContent::new(content_style, self.value)
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell::new(' ', Style::default())
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct ViewportDrawResult {
     pub pitch_low: Pitch,
@@ -17,27 +101,34 @@ pub enum DrawResult {
 }
 
 pub trait DrawComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult>;
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult>;
 
-    fn wb(&self, buffer: &mut Vec<Vec<char>>, pos: &Position, x: usize, y: usize, value: char) {
+    fn wb(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position, x: usize, y: usize, value: Cell) {
         buffer[pos.y + y][pos.x + x] = value;
     }
 
     fn wb_string(
         &self,
-        buffer: &mut Vec<Vec<char>>,
+        buffer: &mut Vec<Vec<Cell>>,
         pos: &Position,
         x: usize,
         y: usize,
         value: String,
+        style: Style,
     ) {
         for (i, char) in value.chars().enumerate() {
             if pos.x + x + i >= buffer[pos.y].len() {
                 break;
             }
-            buffer[pos.y + y][pos.x + x + i] = char;
+            buffer[pos.y + y][pos.x + x + i] = Cell::new(char, style);
         }
     }
+
+    // Restyles an already drawn cell without replacing its character.
+    fn wb_style(&self, buffer: &mut [Vec<Cell>], pos: &Position, x: usize, y: usize, style: Style) {
+        let cell = &mut buffer[pos.y + y][pos.x + x];
+        cell.style = cell.style.overlay(style);
+    }
 }
 
 pub struct Position {
@@ -77,7 +168,7 @@ impl Window {
 }
 
 impl DrawComponent for Window {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
         let mut results = vec![];
         for component in &self.components {
             results.append(component.draw(buffer, &pos).as_mut());
@@ -88,28 +179,30 @@ impl DrawComponent for Window {
 
 pub struct BoxDrawComponent {
     component: Box<dyn DrawComponent>,
+    style: Style,
 }
 
 impl BoxDrawComponent {
-    pub fn new(component: Box<dyn DrawComponent>) -> BoxDrawComponent {
-        BoxDrawComponent { component }
+    pub fn new(component: Box<dyn DrawComponent>, style: Style) -> BoxDrawComponent {
+        BoxDrawComponent { component, style }
     }
 }
 
 impl DrawComponent for BoxDrawComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        let border = |value| Cell::new(value, self.style);
         for x in pos.x + 1..pos.right() {
-            self.wb(buffer, pos, x, 0, BOX_HORIZONTAL);
-            self.wb(buffer, pos, x, pos.h - 1, BOX_HORIZONTAL);
+            self.wb(buffer, pos, x, 0, border(BOX_HORIZONTAL));
+            self.wb(buffer, pos, x, pos.h - 1, border(BOX_HORIZONTAL));
         }
-        for y in pos.y + 1..pos.bottom() {
-            buffer[y][0] = BOX_VERTICAL;
-            buffer[y][pos.x + pos.w - 1] = BOX_VERTICAL;
+        for row in buffer.iter_mut().take(pos.bottom()).skip(pos.y + 1) {
+            row[0] = border(BOX_VERTICAL);
+            row[pos.x + pos.w - 1] = border(BOX_VERTICAL);
         }
-        self.wb(buffer, pos, pos.x, pos.y, BOX_TOP_LEFT);
-        self.wb(buffer, pos, pos.right(), pos.y, BOX_TOP_RIGHT);
-        self.wb(buffer, pos, pos.x, pos.bottom(), BOX_BOTTOM_LEFT);
-        self.wb(buffer, pos, pos.right(), pos.bottom(), BOX_BOTTOM_RIGHT);
+        self.wb(buffer, pos, pos.x, pos.y, border(BOX_TOP_LEFT));
+        self.wb(buffer, pos, pos.right(), pos.y, border(BOX_TOP_RIGHT));
+        self.wb(buffer, pos, pos.x, pos.bottom(), border(BOX_BOTTOM_LEFT));
+        self.wb(buffer, pos, pos.right(), pos.bottom(), border(BOX_BOTTOM_RIGHT));
 
         return self.component.draw(buffer, pos);
     }
@@ -117,6 +210,7 @@ impl DrawComponent for BoxDrawComponent {
 
 pub struct VSplitDrawComponent {
     style: VSplitStyle,
+    divider_style: Style,
     top_component: Box<dyn DrawComponent>,
     bottom_component: Box<dyn DrawComponent>,
 }
@@ -130,11 +224,13 @@ pub enum VSplitStyle {
 impl VSplitDrawComponent {
     pub fn new(
         style: VSplitStyle,
+        divider_style: Style,
         top_component: Box<dyn DrawComponent>,
         bottom_component: Box<dyn DrawComponent>,
     ) -> VSplitDrawComponent {
         VSplitDrawComponent {
             style,
+            divider_style,
             top_component,
             bottom_component,
         }
@@ -142,7 +238,7 @@ impl VSplitDrawComponent {
 }
 
 impl DrawComponent for VSplitDrawComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
         let pos_top = match self.style {
             VSplitStyle::HalfWithDivider => Position {
                 x: pos.x + 1,
@@ -178,31 +274,24 @@ impl DrawComponent for VSplitDrawComponent {
         result.append(self.bottom_component.draw(buffer, &pos_bottom).as_mut());
 
         if self.style == VSplitStyle::HalfWithDivider {
+            let divider = |value| Cell::new(value, self.divider_style);
             for x in 1..pos.w - 1 {
-                self.wb(buffer, pos, x, pos.h / 2 + 1, '═');
+                self.wb(buffer, pos, x, pos.h / 2 + 1, divider(BOX_HORIZONTAL));
             }
-            self.wb(buffer, pos, 0, pos.h / 2 + 1, BOX_LEFT_DIVIDER);
-            self.wb(buffer, pos, pos.w - 1, pos.h / 2 + 1, BOX_RIGHT_DIVIDER);
+            self.wb(buffer, pos, 0, pos.h / 2 + 1, divider(BOX_LEFT_DIVIDER));
+            self.wb(buffer, pos, pos.w - 1, pos.h / 2 + 1, divider(BOX_RIGHT_DIVIDER));
         }
 
         result
     }
 }
 
-pub struct NullComponent {}
-
-impl DrawComponent for NullComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
-        vec![]
-    }
-}
-
 pub struct FillComponent {
-    pub value: char,
+    pub value: Cell,
 }
 
 impl DrawComponent for FillComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
         for x in 0..pos.w {
             for y in 0..pos.h {
                 self.wb(buffer, pos, x, y, self.value);
@@ -0,0 +1,73 @@
+use super::{Cell, DrawComponent, DrawResult};
+use crate::arrangement::Arrangement;
+use crate::draw_components::Position;
+use crate::theme::Theme;
+
+// Lists the patterns, then the clips in time order. The current pattern and the clip
+// under the cursor are highlighted.
+pub struct ArrangementComponent {
+    arrangement: Arrangement,
+    current_pattern: Option<String>,
+    cursor_time_point: u64,
+    theme: Theme,
+}
+
+impl DrawComponent for ArrangementComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        let arrangement = &self.arrangement;
+        if arrangement.patterns.is_empty() {
+            self.wb_string(buffer, pos, 0, 0, "No patterns".to_string(), self.theme.pitch_label);
+            return vec![];
+        }
+
+        let mut row = 0;
+        for pattern in &arrangement.patterns {
+            let uses = arrangement.clips.iter().filter(|clip| clip.pattern == pattern.name).count();
+            let style = if self.current_pattern.as_ref() == Some(&pattern.name) {
+                self.theme.cursor
+            } else {
+                self.theme.pitch_label
+            };
+            let line = format!("{:<4} {} bars, {} clip(s)", pattern.name, pattern.length_b32 / 32, uses);
+            self.wb_string(buffer, pos, 0, row, line, style);
+            row += 1;
+        }
+
+        let mut clips: Vec<_> = arrangement.clips.iter().collect();
+        clips.sort_by_key(|clip| clip.start_b32);
+        let clip_at_cursor = arrangement.clip_at(self.cursor_time_point);
+        for clip in clips.into_iter().take(pos.h.saturating_sub(row)) {
+            let style = if clip_at_cursor == Some(clip) {
+                self.theme.cursor
+            } else {
+                self.theme.pitch_label
+            };
+            let line = format!(
+                "bar {:>3} {:<4} x{} {:+}",
+                clip.start_b32 / 32 + 1,
+                clip.pattern,
+                clip.repeat,
+                clip.transpose
+            );
+            self.wb_string(buffer, pos, 0, row, line, style);
+            row += 1;
+        }
+        vec![]
+    }
+}
+
+impl ArrangementComponent {
+    pub fn new(
+        arrangement: Arrangement,
+        current_pattern: Option<String>,
+        cursor_time_point: u64,
+        theme: Theme,
+    ) -> ArrangementComponent {
+        ArrangementComponent {
+            arrangement,
+            current_pattern,
+            cursor_time_point,
+            theme,
+        }
+    }
+}
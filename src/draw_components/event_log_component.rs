@@ -0,0 +1,24 @@
+use super::{Cell, DrawComponent, DrawResult};
+use crate::draw_components::Position;
+use crate::event_log::EventLog;
+use crate::theme::Theme;
+
+pub struct EventLogComponent {
+    event_log: EventLog,
+    theme: Theme,
+}
+
+impl DrawComponent for EventLogComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        for (row, entry) in self.event_log.tail(pos.h).into_iter().enumerate() {
+            self.wb_string(buffer, pos, 0, row, entry, self.theme.pitch_label);
+        }
+        vec![]
+    }
+}
+
+impl EventLogComponent {
+    pub fn new(event_log: EventLog, theme: Theme) -> EventLogComponent {
+        EventLogComponent { event_log, theme }
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::{Arc, Mutex};
+
+use super::{Cell, DrawComponent, DrawResult};
+use crate::draw_components::Position;
+use crate::score::Score;
+use crate::theme::Theme;
+
+// Lists the notes that fall outside the song key.
+pub struct KeyCheckComponent {
+    score: Arc<Mutex<Score>>,
+    theme: Theme,
+}
+
+impl DrawComponent for KeyCheckComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        let score = self.score.lock().unwrap();
+        let out_of_key = score.notes_out_of_key();
+
+        self.wb_string(
+            buffer,
+            pos,
+            0,
+            0,
+            format!("Key: {}  {} note(s) out of key", score.key, out_of_key.len()),
+            self.theme.pitch_label,
+        );
+
+        // Several notes per row to fit long lists in the panel.
+        let entries: Vec<String> = out_of_key
+            .iter()
+            .map(|note| format!("{:>5} {:<4}", note.onset_b32, note.pitch.as_str()))
+            .collect();
+        let entry_width = 11;
+        let per_row = (pos.w / entry_width).max(1);
+        for (row, chunk) in entries.chunks(per_row).enumerate().take(pos.h.saturating_sub(1)) {
+            self.wb_string(buffer, pos, 0, row + 1, chunk.join(" "), self.theme.pitch_label_out_of_key);
+        }
+        vec![]
+    }
+}
+
+impl KeyCheckComponent {
+    pub fn new(score: Arc<Mutex<Score>>, theme: Theme) -> KeyCheckComponent {
+        KeyCheckComponent { score, theme }
+    }
+}
@@ -0,0 +1,94 @@
+use super::{Cell, DrawComponent, DrawResult};
+use crate::draw_components::{Position, Style};
+use crate::groove::Groove;
+use crate::metronome::Metronome;
+use crate::midi_output::ClockSync;
+use crate::mixer::Mixer;
+use crate::theme::Theme;
+
+const VOLUME_BAR_WIDTH: usize = 20;
+
+pub struct MixerComponent {
+    mixer: Mixer,
+    groove: Groove,
+    clock_sync: ClockSync,
+    metronome: Metronome,
+    theme: Theme,
+}
+
+impl DrawComponent for MixerComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        let groove = &self.groove;
+        let groove_line = format!(
+            "Groove: swing {} {}%  template {}  Clock: {}",
+            groove.swing_unit.as_str(),
+            groove.swing_percent,
+            groove.template.as_str(),
+            self.clock_sync.as_str()
+        );
+        self.wb_string(buffer, pos, 0, 0, groove_line, self.theme.pitch_label);
+        self.wb_string(buffer, pos, 0, 1, self.metronome.as_str(), self.theme.pitch_label);
+
+        for (i, track) in self.mixer.tracks.iter().enumerate().take(pos.h.saturating_sub(2)) {
+            let row = i + 2;
+            let track_style = match self.theme.track_color(i) {
+                Some(color) => Style::fg(color),
+                None => Style::default(),
+            };
+            let label_style = if i == self.mixer.selected_track {
+                self.theme.cursor
+            } else {
+                track_style
+            };
+
+            let filled = track.volume as usize * VOLUME_BAR_WIDTH / 100;
+            let volume_bar = format!(
+                "{}{}",
+                "█".repeat(filled),
+                "·".repeat(VOLUME_BAR_WIDTH - filled)
+            );
+            let mute_str = if track.muted { "[MUTE]" } else { "" };
+            let channel_str = match track.midi_channel {
+                Some(channel) => format!("ch {}", channel + 1),
+                None => "ch -".to_string(),
+            };
+            let swing_str = match groove.track_swing.get(i).copied().flatten() {
+                Some(swing) => format!("swing {}%", swing),
+                None => "swing global".to_string(),
+            };
+
+            let label = format!("Track {:<2}", i + 1);
+            self.wb_string(buffer, pos, 0, row, label.clone(), label_style);
+            let mut x = label.len() + 1;
+            self.wb_string(buffer, pos, x, row, volume_bar, track_style);
+            x += VOLUME_BAR_WIDTH + 1;
+            self.wb_string(
+                buffer,
+                pos,
+                x,
+                row,
+                format!("{:>3}% {:<12} {:<5} {}", track.volume, swing_str, channel_str, mute_str),
+                self.theme.pitch_label,
+            );
+        }
+        vec![]
+    }
+}
+
+impl MixerComponent {
+    pub fn new(
+        mixer: Mixer,
+        groove: Groove,
+        clock_sync: ClockSync,
+        metronome: Metronome,
+        theme: Theme,
+    ) -> MixerComponent {
+        MixerComponent {
+            mixer,
+            groove,
+            clock_sync,
+            metronome,
+            theme,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::{Arc, Mutex};
+
+use super::{Cell, DrawComponent, DrawResult};
+use crate::cursor::Cursor;
+use crate::draw_components::Position;
+use crate::score::Score;
+use crate::theme::Theme;
+
+// Details for the note under the cursor and everything sounding with it.
+pub struct NoteInspectorComponent {
+    score: Arc<Mutex<Score>>,
+    cursor: Cursor,
+    theme: Theme,
+}
+
+impl DrawComponent for NoteInspectorComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        let time_point = self.cursor.time_point();
+        let active_notes = self.score.lock().unwrap().notes_active_at_time(time_point);
+
+        let mut lines = vec![format!(
+            "Cursor: {} at bar {} beat {} (t={})",
+            self.cursor.pitch(),
+            time_point / 32,
+            (time_point % 32) / 8,
+            time_point
+        )];
+
+        match active_notes
+            .iter()
+            .find(|active_note| active_note.note.pitch == self.cursor.pitch())
+        {
+            Some(active_note) => {
+                let note = active_note.note;
+                lines.push(format!(
+                    "Note: {}  onset {}  duration {}  end {}  velocity {}  {:.2} Hz",
+                    note.pitch,
+                    note.onset_b32,
+                    note.duration_b32,
+                    note.onset_b32 + note.duration_b32,
+                    note.velocity,
+                    note.pitch.frequency(note.pitch.octave)
+                ));
+            }
+            None => lines.push("Note: none under cursor".to_string()),
+        }
+
+        let mut sounding: Vec<String> = active_notes
+            .iter()
+            .map(|active_note| active_note.note.pitch.as_str())
+            .collect();
+        sounding.sort();
+        sounding.dedup();
+        lines.push(format!("Sounding: {}", sounding.join(" ")));
+
+        for (row, line) in lines.into_iter().enumerate().take(pos.h) {
+            self.wb_string(buffer, pos, 0, row, line, self.theme.pitch_label);
+        }
+        vec![]
+    }
+}
+
+impl NoteInspectorComponent {
+    pub fn new(score: Arc<Mutex<Score>>, cursor: Cursor, theme: Theme) -> NoteInspectorComponent {
+        NoteInspectorComponent {
+            score,
+            cursor,
+            theme,
+        }
+    }
+}
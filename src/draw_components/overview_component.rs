@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+
+use super::{Cell, DrawComponent, DrawResult, ViewportDrawResult};
+use crate::draw_components::Position;
+use crate::loop_state::LoopState;
+use crate::score::{NoteState, Score};
+use crate::theme::Theme;
+
+const DENSITY_CHARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Whole-song strip, glyph height by the number of note onsets. When the song has more
+// bars than the panel is wide, each column sums several bars.
+pub struct OverviewComponent {
+    score: Arc<Mutex<Score>>,
+    viewport_draw_result: Option<ViewportDrawResult>,
+    playback_time_point: u64,
+    loop_state: LoopState,
+    selected_bar: usize,
+    theme: Theme,
+}
+
+impl DrawComponent for OverviewComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        if pos.h < 3 || pos.w == 0 {
+            return vec![];
+        }
+
+        let density = self.score.lock().unwrap().onsets_per_bar();
+        let viewport_end_bar = self
+            .viewport_draw_result
+            .map_or(0, |result| result.time_point_end.div_ceil(32) as usize);
+        let bar_count = density
+            .len()
+            .max(viewport_end_bar)
+            .max(self.playback_time_point as usize / 32 + 1)
+            .max(self.selected_bar + 1);
+        let bars_per_col = bar_count.div_ceil(pos.w).max(1);
+        let column_density = column_density(&density, bars_per_col);
+        let max_count = column_density.iter().copied().max().unwrap_or(0).max(1);
+
+        for col in 0..bar_count.div_ceil(bars_per_col).min(pos.w) {
+            let first_bar = col * bars_per_col;
+            let last_bar = first_bar + bars_per_col - 1;
+            let count = column_density.get(col).copied().unwrap_or(0);
+            let level = (count * (DENSITY_CHARS.len() - 1)).div_ceil(max_count);
+            self.wb(
+                buffer,
+                pos,
+                col,
+                0,
+                Cell::new(DENSITY_CHARS[level], self.theme.note(0, NoteState::Sustain)),
+            );
+
+            let col_start_b32 = first_bar as u64 * 32;
+            let col_end_b32 = (last_bar as u64 + 1) * 32;
+            let overlaps = |start: u64, end: u64| start < col_end_b32 && end > col_start_b32;
+
+            if let Some(result) = self.viewport_draw_result {
+                if overlaps(result.time_point_start, result.time_point_end) {
+                    self.wb_style(buffer, pos, col, 0, self.theme.overview_viewport);
+                }
+            }
+            if self.loop_state.is_looping() {
+                if let (Some(start), Some(end)) =
+                    (self.loop_state.start_time_b32, self.loop_state.end_time_b32)
+                {
+                    if overlaps(start, end) {
+                        self.wb_style(buffer, pos, col, 0, self.theme.loop_region);
+                    }
+                }
+            }
+            if overlaps(self.playback_time_point, self.playback_time_point + 1) {
+                self.wb_style(buffer, pos, col, 0, self.theme.playhead);
+            }
+            if (first_bar..=last_bar).contains(&self.selected_bar) {
+                self.wb_style(buffer, pos, col, 0, self.theme.cursor);
+            }
+
+            if col % 8 == 0 {
+                self.wb_string(buffer, pos, col, 1, first_bar.to_string(), self.theme.ruler);
+            }
+        }
+
+        self.wb_string(
+            buffer,
+            pos,
+            0,
+            2,
+            format!(
+                "Bar {} of {} ({} bar(s) per column)",
+                self.selected_bar,
+                density.len(),
+                bars_per_col
+            ),
+            self.theme.pitch_label,
+        );
+        vec![]
+    }
+}
+
+// Onsets per column when each column sums `bars_per_col` bars.
+fn column_density(density: &[usize], bars_per_col: usize) -> Vec<usize> {
+    density.chunks(bars_per_col).map(|bars| bars.iter().sum()).collect()
+}
+
+// Keeps the selected bar on one of the song's bars.
+pub fn clamp_bar(bar: usize, bar_count: usize) -> usize {
+    bar.min(bar_count.saturating_sub(1))
+}
+
+impl OverviewComponent {
+    pub fn new(
+        score: Arc<Mutex<Score>>,
+        viewport_draw_result: Option<ViewportDrawResult>,
+        playback_time_point: u64,
+        loop_state: LoopState,
+        selected_bar: usize,
+        theme: Theme,
+    ) -> OverviewComponent {
+        OverviewComponent {
+            score,
+            viewport_draw_result,
+            playback_time_point,
+            loop_state,
+            selected_bar,
+            theme,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_density() {
+        let density = [1, 0, 3, 2, 5];
+        assert_eq!(column_density(&density, 1), vec![1, 0, 3, 2, 5]);
+        assert_eq!(column_density(&density, 2), vec![1, 5, 5]);
+        assert_eq!(column_density(&density, 8), vec![11]);
+        assert!(column_density(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn test_clamp_bar() {
+        assert_eq!(clamp_bar(3, 5), 3);
+        assert_eq!(clamp_bar(5, 5), 4);
+        assert_eq!(clamp_bar(2, 0), 0);
+    }
+}
@@ -0,0 +1,53 @@
+use super::{Cell, DrawComponent, DrawResult};
+use crate::draw_components::Position;
+use crate::panel_view::PanelView;
+use crate::theme::Theme;
+
+// Tab row naming every panel view, with the active view drawn beneath it.
+pub struct PanelComponent {
+    view: PanelView,
+    component: Box<dyn DrawComponent>,
+    theme: Theme,
+}
+
+impl DrawComponent for PanelComponent {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        if pos.h < 2 {
+            return vec![];
+        }
+
+        let mut x = 0;
+        for view in PanelView::all() {
+            let (label, style) = if view == self.view {
+                (format!("[{}]", view.as_str()), self.theme.panel_tab_active)
+            } else {
+                (format!(" {} ", view.as_str()), self.theme.panel_tab)
+            };
+            if x + label.len() >= pos.w {
+                break;
+            }
+            self.wb_string(buffer, pos, x, 0, label.clone(), style);
+            x += label.len() + 1;
+        }
+
+        self.component.draw(
+            buffer,
+            &Position {
+                x: pos.x,
+                y: pos.y + 1,
+                w: pos.w,
+                h: pos.h - 1,
+            },
+        )
+    }
+}
+
+impl PanelComponent {
+    pub fn new(view: PanelView, component: Box<dyn DrawComponent>, theme: Theme) -> PanelComponent {
+        PanelComponent {
+            view,
+            component,
+            theme,
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{mpsc, Arc, Mutex};
 
-use super::{DrawComponent, DrawResult, ViewportDrawResult};
-use crate::cursor::Cursor;
+use super::{Cell, DrawComponent, DrawResult, ViewportDrawResult};
+use crate::cursor::{Cursor, CursorMode};
 use crate::draw_components::Position;
 use crate::events::InputEvent;
 use crate::pitch::Pitch;
@@ -10,8 +10,10 @@ use crate::player::PlayState;
 use crate::score::{ActiveNote, NoteState, Score};
 use crate::score_viewport::ScoreViewport;
 use crate::selection_buffer::SelectionBuffer;
+use crate::theme::Theme;
 use log::debug;
 use crate::loop_state::{LoopState, LoopMode};
+use crate::marker::Marker;
 
 pub struct ScoreDrawComponent {
     score: Arc<Mutex<Score>>,
@@ -21,10 +23,11 @@ pub struct ScoreDrawComponent {
     cursor: Cursor,
     selection_buffer: SelectionBuffer,
     loop_state: LoopState,
+    theme: Theme,
 }
 
 impl DrawComponent for ScoreDrawComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &super::Position) -> Vec<DrawResult> {
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &super::Position) -> Vec<DrawResult> {
         debug!(
             "Drawing score at position: x={}, y={}, w={}, h={}",
             pos.x, pos.y, pos.w, pos.h
@@ -45,6 +48,7 @@ impl DrawComponent for ScoreDrawComponent {
 }
 
 impl ScoreDrawComponent {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         score: Arc<Mutex<Score>>,
         play_state: PlayState,
@@ -53,6 +57,7 @@ impl ScoreDrawComponent {
         cursor: Cursor,
         selection_buffer: SelectionBuffer,
         loop_state: LoopState,
+        theme: Theme,
     ) -> ScoreDrawComponent {
         ScoreDrawComponent {
             score,
@@ -62,11 +67,15 @@ impl ScoreDrawComponent {
             cursor,
             selection_buffer,
             loop_state,
+            theme,
         }
     }
 
     fn visible_pitches(&self, pos: &Position) -> Vec<Pitch> {
         let num_pitches_to_display = pos.h - 1;
+        if self.score_viewport.folded {
+            return self.folded_pitches(num_pitches_to_display);
+        }
 
         let middle_pitch = self.score_viewport.middle_pitch;
         let mut pitches = vec![middle_pitch];
@@ -85,62 +94,89 @@ impl ScoreDrawComponent {
         pitches
     }
 
-    fn draw_score(&self, buffer: &mut Vec<Vec<char>>, pos: &super::Position) -> ViewportDrawResult {
+    // Rows for pitches that have notes plus the cursor's pitch, highest first. When they
+    // don't all fit, the window is centered on the middle pitch but always keeps the cursor.
+    fn folded_pitches(&self, num_pitches_to_display: usize) -> Vec<Pitch> {
+        let mut pitches = self.score.lock().unwrap().used_pitches();
+        if !pitches.contains(&self.cursor.pitch()) {
+            pitches.push(self.cursor.pitch());
+            pitches.sort_by_key(|pitch| pitch.index());
+        }
+        pitches.reverse();
+        if pitches.len() <= num_pitches_to_display {
+            return pitches;
+        }
+
+        let middle_index = self.score_viewport.middle_pitch.index();
+        let middle_row = pitches
+            .iter()
+            .position(|pitch| pitch.index() <= middle_index)
+            .unwrap_or(pitches.len() - 1);
+        let cursor_row = pitches
+            .iter()
+            .position(|pitch| *pitch == self.cursor.pitch())
+            .unwrap();
+        let mut first_row = middle_row
+            .saturating_sub(num_pitches_to_display / 2)
+            .min(pitches.len() - num_pitches_to_display);
+        if cursor_row < first_row {
+            first_row = cursor_row;
+        } else if cursor_row >= first_row + num_pitches_to_display {
+            first_row = cursor_row + 1 - num_pitches_to_display;
+        }
+        pitches[first_row..first_row + num_pitches_to_display].to_vec()
+    }
+
+    fn draw_score(&self, buffer: &mut Vec<Vec<Cell>>, pos: &super::Position) -> ViewportDrawResult {
         let pitches = self.visible_pitches(pos);
-        let mut time_point = self.score_viewport.time_point;
         debug!("Drawing score with {} visible pitches", pitches.len());
 
-        // Draw the empty score.
+        let col_duration = self.score_viewport.zoom.duration_b32();
+
+        // Draw the empty score. Past one bar per column, only every fourth bar gets a line.
+        let bar_line_every_b32 = if col_duration < 32 { 32 } else { 128 };
+        let mut markers = self.score.lock().unwrap().markers.clone();
+        // The song end is labelled like a marker, unless a marker is there
+        markers.push(Marker::new(self.score.lock().unwrap().song_length_b32(), "End"));
+        let mut ruler_free_from_col = 0;
         for col in 0..pos.w - 1 {
-            let bar_col = col % (self.score_viewport.resolution.bar_length_in_beats()) == 0;
+            let time_point_at_col = self.score_viewport.time_point + (col as u64) * col_duration;
+            let bar_col = (time_point_at_col..time_point_at_col + col_duration)
+                .any(|time_point| time_point % bar_line_every_b32 == 0);
             for (row, _pitch) in pitches.iter().enumerate() {
-                let draw_char = if bar_col { '⎸' } else { '.' };
-                self.wb(buffer, pos, col, row, draw_char);
+                let draw_cell = if bar_col {
+                    Cell::new('⎸', self.theme.bar_line)
+                } else {
+                    Cell::new('.', self.theme.grid)
+                };
+                self.wb(buffer, pos, col, row, draw_cell);
             }
 
-            if bar_col {
-                let time_point_at_col = self.score_viewport.time_point
-                    + (col as u64) * self.score_viewport.resolution.duration_b32();
-                self.wb_string(
-                    buffer,
-                    pos,
-                    col,
-                    pitches.len(),
-                    (time_point_at_col / (32)).to_string(),
-                );
+            // Marker names take the ruler over bar numbers, cut short at the edge
+            let marker = markers.iter().find(|marker| {
+                marker.time_b32 >= time_point_at_col && marker.time_b32 < time_point_at_col + col_duration
+            });
+            if let Some(marker) = marker.filter(|_| col >= ruler_free_from_col) {
+                let label: String = marker.name.chars().take(pos.w - 1 - col).collect();
+                ruler_free_from_col = col + label.chars().count() + 1;
+                self.wb_string(buffer, pos, col, pitches.len(), label, self.theme.marker);
+                continue;
             }
-        }
 
-        // Draw the playhead and loop markers
-        let mut time_point = self.score_viewport.time_point;
-        for col in 0..pos.w - 1 {
-            for _ in 0..self.score_viewport.resolution.duration_b32() {
-                for (row, _pitch) in pitches.iter().enumerate() {
-                    if time_point == self.score_viewport.playback_time_point {
-                        self.wb(buffer, pos, col, row, '░');
-                    } else if self.loop_state.mode == LoopMode::Looping {
-                        // Show loop start/end markers if loop mode is enabled
-                        if let Some(start_time) = self.loop_state.start_time_b32 {
-                            if time_point == start_time {
-                                self.wb(buffer, pos, col, row, '░');
-                            }
-                        }
-                        if let Some(end_time) = self.loop_state.end_time_b32 {
-                            if time_point == end_time {
-                                self.wb(buffer, pos, col, row, '░');
-                            }
-                        }
-                    }
-                }
-                time_point += 1;
+            // Skip labels that would run into the previous one when zoomed out.
+            let label = time_point_at_col.div_ceil(32).to_string();
+            if bar_col && col >= ruler_free_from_col && col + label.len() < pos.w - 1 {
+                ruler_free_from_col = col + label.len() + 1;
+                self.wb_string(buffer, pos, col, pitches.len(), label, self.theme.ruler);
             }
         }
 
         let mut time_point = self.score_viewport.time_point;
         for col in 0..pos.w - 1 {
             let mut col_states: HashMap<(usize, Pitch), NoteState> = HashMap::new();
+            let mut buffered_rows: HashSet<usize> = HashSet::new();
 
-            for _ in 0..self.score_viewport.resolution.duration_b32() {
+            for _ in 0..col_duration {
                 let active_notes = self.score.lock().unwrap().notes_active_at_time(time_point);
 
                 for (row, pitch) in pitches.iter().enumerate() {
@@ -150,15 +186,15 @@ impl ScoreDrawComponent {
                         let current_state = col_states
                             .entry((row, *pitch))
                             .or_insert(NoteState::Sustain);
+                        // A column can span several notes when zoomed out; show any onset.
                         match active_note.state {
-                            NoteState::Onset | NoteState::Release => {
-                                *current_state = active_note.state
-                            }
-                            NoteState::Sustain => {
-                                if *current_state == NoteState::Sustain {
-                                    *current_state = NoteState::Sustain
+                            NoteState::Onset => *current_state = NoteState::Onset,
+                            NoteState::Release => {
+                                if *current_state != NoteState::Onset {
+                                    *current_state = NoteState::Release
                                 }
                             }
+                            NoteState::Sustain => (),
                         }
                     }
                 }
@@ -172,15 +208,8 @@ impl ScoreDrawComponent {
 
                     for (row, pitch) in pitches.iter().enumerate() {
                         if let Some(active_note) = selected_notes_map.get(pitch) {
-                            let current_state = col_states
-                                .entry((row, *pitch))
-                                .or_insert(NoteState::Sustain);
-                            *current_state = active_note.state;
-                            match active_note.state {
-                                NoteState::Onset => *current_state = NoteState::Onset,
-                                NoteState::Sustain => *current_state = NoteState::Sustain,
-                                NoteState::Release => *current_state = NoteState::Release,
-                            }
+                            col_states.insert((row, *pitch), active_note.state);
+                            buffered_rows.insert(row);
                         }
                     }
                 }
@@ -188,25 +217,71 @@ impl ScoreDrawComponent {
                 time_point += 1;
             }
 
-            for ((row, pitch), state) in col_states {
+            for ((row, _pitch), state) in col_states {
                 let note_char = match state {
                     NoteState::Onset => '█',
                     NoteState::Sustain => '░',
                     NoteState::Release => '▒',
                 };
-                self.wb(buffer, pos, col, row, note_char);
+                let note_style = if buffered_rows.contains(&row) {
+                    self.theme.selection_buffer
+                } else {
+                    self.theme.note(0, state)
+                };
+                self.wb(buffer, pos, col, row, Cell::new(note_char, note_style));
+            }
+        }
+
+        // Shade the loop region, then draw the playhead and loop markers over the notes.
+        // Columns with a marker go unshaded so it stands out without color.
+        let loop_region = self.loop_state.region().filter(|_| self.loop_state.mode == LoopMode::Looping);
+        let mut time_point = self.score_viewport.time_point;
+        for col in 0..pos.w - 1 {
+            if let Some((start, end)) = loop_region {
+                let col_end = time_point + col_duration;
+                let col_has_marker = (time_point..col_end).any(|time_point| {
+                    time_point == self.score_viewport.playback_time_point || time_point == start || time_point == end
+                });
+                if time_point < end && col_end > start && !col_has_marker {
+                    for row in 0..pitches.len() {
+                        self.wb_style(buffer, pos, col, row, self.theme.loop_shade);
+                    }
+                }
+            }
+            for _ in 0..col_duration {
+                let marker_style = if time_point == self.score_viewport.playback_time_point {
+                    Some(self.theme.playhead)
+                } else if self.loop_state.mode == LoopMode::Looping
+                    && (self.loop_state.start_time_b32 == Some(time_point)
+                        || self.loop_state.end_time_b32 == Some(time_point))
+                {
+                    // Show loop start/end markers if loop mode is enabled
+                    Some(self.theme.loop_region)
+                } else {
+                    None
+                };
+                if let Some(style) = marker_style {
+                    for row in 0..pitches.len() {
+                        self.wb_style(buffer, pos, col, row, style);
+                    }
+                }
+                time_point += 1;
             }
         }
 
         // Draw the cursor - iterate over each time point and pitch.
+        let cursor_style = match self.cursor.mode() {
+            CursorMode::Select(_, _) => self.theme.selection,
+            _ => self.theme.cursor,
+        };
         let mut time_point = self.score_viewport.time_point;
         for col in 0..pos.w - 1 {
             for (row, pitch) in pitches.iter().enumerate() {
-                if self.cursor.visible_at(*pitch, time_point) {
-                    self.wb(buffer, pos, col, row, 'C');
+                if self.cursor.visible_between(*pitch, time_point, time_point + col_duration) {
+                    self.wb_style(buffer, pos, col, row, cursor_style);
                 }
             }
-            time_point += self.score_viewport.resolution.duration_b32();
+            time_point += col_duration;
         }
 
         ViewportDrawResult {
@@ -217,9 +292,105 @@ impl ScoreDrawComponent {
         }
     }
 
-    fn draw_pitches(&self, buffer: &mut Vec<Vec<char>>, pos: &super::Position) {
+    fn draw_pitches(&self, buffer: &mut Vec<Vec<Cell>>, pos: &super::Position) {
+        let key = self.score.lock().unwrap().key;
         for (i, pitch) in self.visible_pitches(pos).iter().enumerate() {
-            self.wb_string(buffer, pos, 0, i, pitch.as_str());
+            let style = if !key.contains(*pitch) {
+                self.theme.pitch_label_out_of_key
+            } else if key.is_tonic(*pitch) {
+                self.theme.pitch_label_tonic
+            } else {
+                self.theme.pitch_label
+            };
+            self.wb_string(buffer, pos, 0, i, pitch.as_str(), style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw_components::Style;
+    use crate::pitch::Tone;
+    use crate::resolution::Resolution;
+    use crate::score::note;
+    use crate::zoom::Zoom;
+
+    fn create_test_score() -> Score {
+        let mut score = Score::new(120);
+        for (tone, onset_b32, duration_b32) in [(Tone::C, 0, 32), (Tone::E, 3, 5), (Tone::D, 37, 30)] {
+            score.insert_note(note(Pitch::new(tone, 4), onset_b32, duration_b32));
         }
+        score
+    }
+
+    // Draws `cols` columns of the folded score and returns the note rows, grid as dots
+    fn render(zoom: Zoom, time_point: u64, cols: usize) -> (Vec<String>, ViewportDrawResult) {
+        let (tx, _rx) = mpsc::channel();
+        let mut score_viewport = ScoreViewport::new(
+            Pitch::new(Tone::C, 4),
+            Resolution::Time1_16,
+            zoom,
+            time_point,
+            0,
+        );
+        score_viewport.folded = true;
+        let component = ScoreDrawComponent::new(
+            Arc::new(Mutex::new(create_test_score())),
+            PlayState::Stopped,
+            score_viewport,
+            tx,
+            Cursor::new(Pitch::new(Tone::C, 4), 0),
+            SelectionBuffer::None,
+            LoopState::new(),
+            Theme::monochrome(),
+        );
+        let pos = Position { x: 0, y: 0, w: 4 + cols + 1, h: 4 };
+        let mut buffer = vec![vec![Cell::new(' ', Style::default()); pos.w]; pos.h];
+        let results = component.draw(&mut buffer, &pos);
+        let DrawResult::ViewportDrawResult(result) = results[0];
+        let rows = buffer[..3]
+            .iter()
+            .map(|row| {
+                row[4..4 + cols]
+                    .iter()
+                    .map(|cell| if cell.value == '⎸' { '.' } else { cell.value })
+                    .collect()
+            })
+            .collect();
+        (rows, result)
+    }
+
+    #[test]
+    fn test_zoom_column_mapping() {
+        // Rows are E4, D4, C4; E4 starts off the 1/16 grid at 3
+        let (rows, result) = render(Zoom::Time1_32, 0, 10);
+        assert_eq!(rows, ["...█░░░▒..", "..........", "█░░░░░░░░░"]);
+        assert_eq!(result.time_point_end, 10);
+
+        // An onset anywhere in a column wins over the rest of the note
+        let (rows, result) = render(Zoom::Time1_16, 0, 5);
+        assert_eq!(rows, [".█░▒.", ".....", "█░░░░"]);
+        assert_eq!(result.time_point_end, 10);
+
+        // Wider than 1/4 per column, several notes share a column
+        let (rows, result) = render(Zoom::Time1_2, 0, 5);
+        assert_eq!(rows, ["█....", "..█░▒", "█▒..."]);
+        assert_eq!(result.time_point_end, 80);
+
+        let (rows, result) = render(Zoom::Time1_1, 0, 3);
+        assert_eq!(rows, ["█..", ".█▒", "█.."]);
+        assert_eq!(result.time_point_end, 96);
+    }
+
+    #[test]
+    fn test_viewport_end_ignores_resolution() {
+        // The viewport spans whole zoom columns even when it starts off the edit grid
+        let (rows, result) = render(Zoom::Time1_2, 35, 5);
+        assert_eq!(rows[1], "█▒...");
+        assert_eq!(result.time_point_start, 35);
+        assert_eq!(result.time_point_end, 35 + 5 * 16);
+        assert_eq!(result.pitch_high, Pitch::new(Tone::E, 4));
+        assert_eq!(result.pitch_low, Pitch::new(Tone::C, 4));
     }
 }
@@ -1,20 +1,33 @@
-use std::sync::{Arc, Mutex};
-
-use super::{DrawComponent, DrawResult};
+use super::{Cell, DrawComponent, DrawResult};
 use crate::cursor::Cursor;
 use crate::draw_components::Position;
+use crate::grab::GrabHandle;
+use crate::selection_buffer::PasteMode;
+use crate::selection_range::SelectionMode;
 use crate::score_viewport::ScoreViewport;
 use crate::loop_state::{LoopState, LoopMode};
+use crate::theme::Theme;
 
 pub struct StatusBarComponent {
     cursor: Cursor,
+    grab_handle: Option<GrabHandle>,
+    selection_mode: SelectionMode,
+    register: Option<char>,
+    arrangement_label: Option<String>,
+    step_entry: bool,
+    keys_label: Option<String>,
+    record_label: Option<String>,
+    return_on_stop: bool,
+    paste_mode: PasteMode,
+    paste_repeat: u64,
     score_viewport: ScoreViewport,
     loop_state: LoopState,
+    theme: Theme,
 }
 
 impl DrawComponent for StatusBarComponent {
-    fn draw(&self, buffer: &mut Vec<Vec<char>>, pos: &Position) -> Vec<DrawResult> {
-        self.wb_string(buffer, pos, 0, 0, "|".repeat(pos.w));
+    fn draw(&self, buffer: &mut Vec<Vec<Cell>>, pos: &Position) -> Vec<DrawResult> {
+        self.wb_string(buffer, pos, 0, 0, "|".repeat(pos.w), self.theme.status_bar);
         
         let loop_str = match self.loop_state.mode {
             LoopMode::Disabled => "[LOOP:OFF]".to_string(),
@@ -27,25 +40,81 @@ impl DrawComponent for StatusBarComponent {
             }
         };
 
+        let mut mode_str = match self.grab_handle {
+            Some(handle) => format!("[GRAB:{}] ", handle.as_str()),
+            None => String::new(),
+        };
+        if let Some(register) = self.register {
+            mode_str.push_str(&format!("[REG:\"{}] ", register));
+        }
+        if let Some(arrangement_label) = &self.arrangement_label {
+            mode_str.push_str(&format!("[{}] ", arrangement_label));
+        }
+        if self.step_entry {
+            mode_str.push_str("[STEP] ");
+        }
+        if let Some(keys_label) = &self.keys_label {
+            mode_str.push_str(&format!("[{}] ", keys_label));
+        }
+        if let Some(record_label) = &self.record_label {
+            mode_str.push_str(&format!("[{}] ", record_label));
+        }
+        if self.return_on_stop {
+            mode_str.push_str("[RETURN] ");
+        }
+
         let status_str = format!(
-            "{} [Cursor: {}] [Score Viewport: {}]",
-            loop_str, self.cursor, self.score_viewport
+            "{}{} [Select: {}] [Paste: {}{}] [Grid: {}] [Zoom: {}] [Rows: {}] [Follow: {}] [Cursor: {}] [Score Viewport: {}]",
+            mode_str,
+            loop_str,
+            self.selection_mode.as_str(),
+            self.paste_mode.as_str(),
+            if self.paste_repeat > 1 { format!(" x{}", self.paste_repeat) } else { String::new() },
+            self.score_viewport.resolution.as_str(),
+            self.score_viewport.zoom.as_str(),
+            if self.score_viewport.folded { "used" } else { "all" },
+            self.score_viewport.follow.as_str(),
+            self.cursor,
+            self.score_viewport
         );
-        self.wb_string(buffer, pos, 0, 0, status_str);
+        self.wb_string(buffer, pos, 0, 0, status_str, self.theme.status_bar);
         vec![]
     }
 }
 
 impl StatusBarComponent {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cursor: Cursor,
+        grab_handle: Option<GrabHandle>,
+        selection_mode: SelectionMode,
+        register: Option<char>,
+        arrangement_label: Option<String>,
+        step_entry: bool,
+        keys_label: Option<String>,
+        record_label: Option<String>,
+        return_on_stop: bool,
+            paste_mode: PasteMode,
+        paste_repeat: u64,
         score_viewport: ScoreViewport,
         loop_state: LoopState,
+        theme: Theme,
     ) -> StatusBarComponent {
         StatusBarComponent {
             cursor,
+            grab_handle,
+            selection_mode,
+            register,
+            arrangement_label,
+            step_entry,
+            keys_label,
+            record_label,
+            return_on_stop,
+            paste_mode,
+            paste_repeat,
             score_viewport,
             loop_state,
+            theme,
         }
     }
 }
@@ -0,0 +1,37 @@
+use chrono::Local;
+use std::collections::VecDeque;
+
+const EVENT_LOG_CAPACITY: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    entries: VecDeque<String>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::with_capacity(EVENT_LOG_CAPACITY),
+        }
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.entries.len() == EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries
+            .push_back(format!("{} {}", Local::now().format("%H:%M:%S"), message));
+    }
+
+    // Most recent entries, oldest first.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let skip = self.entries.len().saturating_sub(count);
+        self.entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,15 +1,31 @@
-use crossterm::event::{poll, read, Event, KeyCode};
+use crossterm::event::{
+    poll, read, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind, KeyModifiers,
+    KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
 use std::io;
 use std::sync::mpsc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::keyboard_piano::{key_semitone, HeldKeys};
+
+use crate::pitch::Pitch;
+use crate::transform::Transform;
+
+#[derive(Debug)]
 pub enum InputEvent {
     ViewerBarNext,
     ViewerBarPrevious,
     ViewerResolutionIncrease,
     ViewerResolutionDecrease,
+    ViewerZoomIn,
+    ViewerZoomOut,
     ViewerOctaveIncrease,
     ViewerOctaveDecrease,
+    ViewerSemitoneIncrease,
+    ViewerSemitoneDecrease,
+    ViewerFitToContent,
+    ViewerToggleFold,
     PlayerTogglePlayback,
     Quit,
     PlayerBeatChange(u64),
@@ -27,23 +43,181 @@ pub enum InputEvent {
     SetLoopTimes,
     SaveSong,
     SelectIn,
+    ToggleTheme,
+    CyclePanelView,
+    MixerVolumeUp,
+    MixerVolumeDown,
+    MixerToggleMute,
+    KeyTonicNext,
+    KeyScaleNext,
+    KeyToggleCursorTone,
+    ToggleScaleSnap,
+    OverviewBarPrevious,
+    OverviewBarNext,
+    OverviewJump,
+    Transform(Transform),
+    Undo,
+    Redo,
+    Quantize,
+    QuantizeTargetNext,
+    QuantizeStrengthNext,
+    QuantizeSwingNext,
+    Humanize,
+    GrooveSwingNext,
+    GrooveSwingUnitToggle,
+    GrooveTemplateNext,
+    GrooveTrackSwingNext,
+    GrabToggle,
+    GrabHandleNext,
+    SelectNote,
+    SelectAll,
+    SelectPitch,
+    ToggleSelectionMode,
+    SelectRegister(char),
+    ClipboardHistoryNext,
+    CopyToSystemClipboard,
+    PasteText(String),
+    PasteModeNext,
+    PasteRepeatNext,
+    InsertBar,
+    DeleteBar,
+    PatternFromSelection,
+    PatternNext,
+    PatternEditToggle,
+    ClipPlace,
+    ClipRepeat(i64),
+    ClipTranspose(i32),
+    ClipDelete,
+    ArrangementFlatten,
+    MidiNoteOn(Pitch, u8),
+    MidiNoteOff(Pitch),
+    StepEntryToggle,
+    RecordToggle,
+    RecordQuantizeToggle,
+    MidiClock,
+    MidiStart,
+    MidiContinue,
+    MidiStop,
+    ClockSyncNext,
+    MixerMidiChannelNext,
+    KeysModeToggle,
+    KeysNoteOn(u8), // Semitones above C of the keys octave
+    KeysNoteOff(u8),
+    KeysOctaveUp,
+    KeysOctaveDown,
+    MetronomeModeNext,
+    MetronomeVolumeNext,
+    CountInNext,
+    LoopFromCursor,
+    LoopNudge(i64), // Grid steps
+    LoopSnapToBar,
+    LoopDouble,
+    LoopHalve,
+    MarkerSet,
+    MarkerDelete,
+    MarkerNext,
+    MarkerPrevious,
+    MarkerNameEntry,
+    MarkerRename(String),
+    LoopSection,
+    ExportMidi,
+    PlayFromCursor,
+    PlayFromViewport,
+    PlaySelection,
+    ReturnOnStopToggle,
+    FollowModeNext,
+    Scrub(i64),
+    SongEndAtCursor,
 }
 
 pub fn capture_input(tx: &mpsc::Sender<InputEvent>) -> io::Result<()> {
     crossterm::terminal::enable_raw_mode()?;
+    // Text pasted into the terminal arrives as one event instead of key presses
+    execute!(io::stdout(), EnableBracketedPaste)?;
     let mut alt_pressed = false;
+    let mut register_pending = false;
+    // Name being typed for the marker at the cursor
+    let mut marker_name: Option<String> = None;
+    // Set while the keyboard plays notes
+    let mut keys_mode: Option<HeldKeys> = None;
 
     loop {
-        if poll(Duration::from_millis(500))? {
-            if let Event::Key(event) = read()? {
-                // Unmapped:
-                // 3, 4, q, w, x
+        if let Some(held_keys) = &mut keys_mode {
+            for semitone in held_keys.expire(Instant::now()) {
+                tx.send(InputEvent::KeysNoteOff(semitone)).unwrap();
+            }
+        }
+        // Poll often while fixed length notes wait to end
+        let timeout_ms = match &keys_mode {
+            Some(held_keys) if !held_keys.is_empty() => 20,
+            _ => 500,
+        };
+        if poll(Duration::from_millis(timeout_ms))? {
+            let event = read()?;
+            if let Event::Paste(text) = event {
+                tx.send(InputEvent::PasteText(text)).unwrap();
+                continue;
+            }
+            if let Event::Key(event) = event {
+                // While naming a marker keys type its name, Enter sets it and Esc gives up
+                if let Some(mut name) = marker_name.take() {
+                    match event.code {
+                        _ if event.kind == KeyEventKind::Release => marker_name = Some(name),
+                        KeyCode::Char(c) => {
+                            name.push(c);
+                            marker_name = Some(name);
+                        }
+                        KeyCode::Backspace => {
+                            name.pop();
+                            marker_name = Some(name);
+                        }
+                        KeyCode::Enter => tx.send(InputEvent::MarkerRename(name)).unwrap(),
+                        KeyCode::Esc => (),
+                        _ => marker_name = Some(name),
+                    }
+                    continue;
+                }
+                // The key after '"' names the register, as in vim
+                if register_pending && event.kind != KeyEventKind::Release {
+                    register_pending = false;
+                    if let KeyCode::Char(name) = event.code {
+                        tx.send(InputEvent::SelectRegister(name)).unwrap();
+                    }
+                    continue;
+                }
+                // In keys mode lowercase letters, digits and ',' are piano keys or do nothing,
+                // apart from cancel and quit. Undo and redo are piano keys until '/' ends it.
+                if let (Some(held_keys), KeyCode::Char(c)) = (&mut keys_mode, event.code) {
+                    let control_key = c == '1' || c == 'p';
+                    if (c.is_ascii_lowercase() || c.is_ascii_digit() || c == ',') && !control_key {
+                        if let Some(semitone) = key_semitone(c) {
+                            if event.kind == KeyEventKind::Release {
+                                if held_keys.release(semitone) {
+                                    tx.send(InputEvent::KeysNoteOff(semitone)).unwrap();
+                                }
+                            } else if held_keys.press(semitone, Instant::now()) {
+                                tx.send(InputEvent::KeysNoteOn(semitone)).unwrap();
+                            }
+                        }
+                        continue;
+                    }
+                }
+                // Other keys act on press and auto-repeat
+                if event.kind == KeyEventKind::Release {
+                    continue;
+                }
                 match event.code {
                     // Core navigation and alt key
                     KeyCode::Char('1') => tx.send(InputEvent::Cancel).unwrap(),
                     KeyCode::Char('2') => alt_pressed = !alt_pressed,
 
-                    // Arrow keys - Cursor movement or Viewport navigation
+                    // Arrow keys - Cursor movement or Viewport navigation; held with shift they scrub
+                    KeyCode::Left if event.modifiers.contains(KeyModifiers::SHIFT) => {
+                        tx.send(InputEvent::Scrub(-1)).unwrap();
+                    }
+                    KeyCode::Right if event.modifiers.contains(KeyModifiers::SHIFT) => {
+                        tx.send(InputEvent::Scrub(1)).unwrap();
+                    }
                     KeyCode::Left => {
                         tx.send(if alt_pressed {
                             InputEvent::ViewerBarPrevious
@@ -79,23 +253,165 @@ pub fn capture_input(tx: &mpsc::Sender<InputEvent>) -> io::Result<()> {
 
                     // Most common operations - top row right side
                     KeyCode::Char('r') => tx.send(InputEvent::InsertNote).unwrap(),
-                    // TODO: Delete is not working for a single note.
+                    // Deletes the selection, or the note under the cursor
                     KeyCode::Char('f') => tx.send(InputEvent::Delete).unwrap(),
 
                     // Selection controls - grouped together
                     KeyCode::Char('e') => tx.send(InputEvent::SelectIn).unwrap(),
+                    KeyCode::Char('5') => tx.send(InputEvent::SelectNote).unwrap(),
+                    KeyCode::Char('6') => tx.send(InputEvent::SelectAll).unwrap(),
+                    KeyCode::Char('7') => tx.send(InputEvent::SelectPitch).unwrap(),
+                    KeyCode::Char('8') => tx.send(InputEvent::ToggleSelectionMode).unwrap(),
+
+                    // Grab the note or selection under the cursor, arrows then move or resize it
+                    KeyCode::Char('4') => tx.send(InputEvent::GrabToggle).unwrap(),
+                    KeyCode::Tab => tx.send(InputEvent::GrabHandleNext).unwrap(),
 
                     // Clipboard operations - grouped on left side
                     KeyCode::Char('a') => tx.send(InputEvent::Yank).unwrap(),
                     KeyCode::Char('s') => tx.send(InputEvent::Cut).unwrap(),
                     KeyCode::Char('d') => tx.send(InputEvent::Paste).unwrap(),
+                    // Ripple edits at the cursor's bar
+                    KeyCode::Char('I') => tx.send(InputEvent::InsertBar).unwrap(),
+                    KeyCode::Char('K') => tx.send(InputEvent::DeleteBar).unwrap(),
+                    KeyCode::Char('V') => tx.send(InputEvent::PasteModeNext).unwrap(),
+                    KeyCode::Char('B') => tx.send(InputEvent::PasteRepeatNext).unwrap(),
+                    KeyCode::Char('"') => register_pending = true,
+                    KeyCode::Char('P') => tx.send(InputEvent::ClipboardHistoryNext).unwrap(),
+                    KeyCode::Char('C') => tx.send(InputEvent::CopyToSystemClipboard).unwrap(),
+
+                    // Patterns and clips
+                    KeyCode::Char('G') => tx.send(InputEvent::PatternFromSelection).unwrap(),
+                    KeyCode::Char('L') => tx.send(InputEvent::PatternNext).unwrap(),
+                    KeyCode::Char('`') => tx.send(InputEvent::PatternEditToggle).unwrap(),
+                    KeyCode::Char('M') => tx.send(InputEvent::ClipPlace).unwrap(),
+                    KeyCode::Char('}') => tx.send(InputEvent::ClipRepeat(1)).unwrap(),
+                    KeyCode::Char('{') => tx.send(InputEvent::ClipRepeat(-1)).unwrap(),
+                    KeyCode::Char(')') => tx.send(InputEvent::ClipTranspose(1)).unwrap(),
+                    KeyCode::Char('(') => tx.send(InputEvent::ClipTranspose(-1)).unwrap(),
+                    KeyCode::Char('|') => tx.send(InputEvent::ClipDelete).unwrap(),
+                    KeyCode::Char('~') => tx.send(InputEvent::ArrangementFlatten).unwrap(),
+
+                    // MIDI step entry and recording
+                    KeyCode::Char('U') => tx.send(InputEvent::StepEntryToggle).unwrap(),
+                    KeyCode::Char('O') => tx.send(InputEvent::RecordToggle).unwrap(),
+                    KeyCode::Char('X') => tx.send(InputEvent::RecordQuantizeToggle).unwrap(),
+                    KeyCode::Char('Z') => tx.send(InputEvent::ClockSyncNext).unwrap(),
+
+                    // Computer keyboard as a piano
+                    KeyCode::Char('/') => {
+                        match keys_mode.take() {
+                            Some(mut held_keys) => {
+                                for semitone in held_keys.release_all() {
+                                    tx.send(InputEvent::KeysNoteOff(semitone)).unwrap();
+                                }
+                                if held_keys.key_release() {
+                                    execute!(io::stdout(), PopKeyboardEnhancementFlags)?;
+                                }
+                            }
+                            None => {
+                                // Key release needs every key reported as an escape code
+                                let key_release = crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false);
+                                if key_release {
+                                    execute!(
+                                        io::stdout(),
+                                        PushKeyboardEnhancementFlags(
+                                            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                                                | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                                                | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+                                                | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+                                        )
+                                    )?;
+                                }
+                                keys_mode = Some(HeldKeys::new(key_release));
+                            }
+                        }
+                        tx.send(InputEvent::KeysModeToggle).unwrap();
+                    }
+                    KeyCode::Char('>') => tx.send(InputEvent::KeysOctaveUp).unwrap(),
+                    KeyCode::Char('<') => tx.send(InputEvent::KeysOctaveDown).unwrap(),
 
                     // Loop controls - grouped together
                     KeyCode::Char('c') => tx.send(InputEvent::ToggleLoopMode).unwrap(),
                     KeyCode::Char('v') => tx.send(InputEvent::SetLoopTimes).unwrap(),
+                    KeyCode::Char('$') => tx.send(InputEvent::LoopFromCursor).unwrap(),
+                    KeyCode::Char(';') => tx.send(InputEvent::LoopNudge(-1)).unwrap(),
+                    KeyCode::Char('\'') => tx.send(InputEvent::LoopNudge(1)).unwrap(),
+                    KeyCode::Char('%') => tx.send(InputEvent::LoopSnapToBar).unwrap(),
+                    KeyCode::Char('*') => tx.send(InputEvent::LoopDouble).unwrap(),
+                    KeyCode::Char('&') => tx.send(InputEvent::LoopHalve).unwrap(),
+                    KeyCode::Char(':') => tx.send(InputEvent::LoopSection).unwrap(),
+
+                    // Markers and sections
+                    KeyCode::Char('^') => tx.send(InputEvent::MarkerSet).unwrap(),
+                    KeyCode::Delete => tx.send(InputEvent::MarkerDelete).unwrap(),
+                    KeyCode::End => tx.send(InputEvent::MarkerNext).unwrap(),
+                    KeyCode::Home => tx.send(InputEvent::MarkerPrevious).unwrap(),
+                    KeyCode::Insert => {
+                        marker_name = Some(String::new());
+                        tx.send(InputEvent::MarkerNameEntry).unwrap();
+                    }
 
                     // Save and quit - bottom row
                     KeyCode::Char('z') => tx.send(InputEvent::SaveSong).unwrap(),
+                    KeyCode::F(5) => tx.send(InputEvent::ExportMidi).unwrap(),
+                    KeyCode::Char('x') => tx.send(InputEvent::ToggleTheme).unwrap(),
+
+                    // Bottom panel
+                    KeyCode::Char('3') => tx.send(InputEvent::CyclePanelView).unwrap(),
+                    KeyCode::Char('m') => tx.send(InputEvent::MixerToggleMute).unwrap(),
+                    KeyCode::Char('=') => tx.send(InputEvent::MixerVolumeUp).unwrap(),
+                    KeyCode::Char('-') => tx.send(InputEvent::MixerVolumeDown).unwrap(),
+                    KeyCode::Char('_') => tx.send(InputEvent::MixerMidiChannelNext).unwrap(),
+                    KeyCode::Char('!') => tx.send(InputEvent::MetronomeModeNext).unwrap(),
+                    KeyCode::Char('@') => tx.send(InputEvent::MetronomeVolumeNext).unwrap(),
+                    KeyCode::Char('#') => tx.send(InputEvent::CountInNext).unwrap(),
+                    // Vertical scrolling
+                    KeyCode::PageUp => tx.send(InputEvent::ViewerOctaveIncrease).unwrap(),
+                    KeyCode::PageDown => tx.send(InputEvent::ViewerOctaveDecrease).unwrap(),
+                    KeyCode::Char('i') => tx.send(InputEvent::ViewerSemitoneIncrease).unwrap(),
+                    KeyCode::Char('k') => tx.send(InputEvent::ViewerSemitoneDecrease).unwrap(),
+                    KeyCode::Char('o') => tx.send(InputEvent::ViewerFitToContent).unwrap(),
+                    KeyCode::Char('l') => tx.send(InputEvent::ViewerToggleFold).unwrap(),
+
+                    // Key and scale
+                    KeyCode::Char('t') => tx.send(InputEvent::KeyTonicNext).unwrap(),
+                    KeyCode::Char('g') => tx.send(InputEvent::KeyScaleNext).unwrap(),
+                    KeyCode::Char('b') => tx.send(InputEvent::KeyToggleCursorTone).unwrap(),
+                    KeyCode::Char('u') => tx.send(InputEvent::ToggleScaleSnap).unwrap(),
+
+                    // Display zoom
+                    KeyCode::Char('.') => tx.send(InputEvent::ViewerZoomIn).unwrap(),
+                    KeyCode::Char(',') => tx.send(InputEvent::ViewerZoomOut).unwrap(),
+
+                    KeyCode::Char('[') => tx.send(InputEvent::OverviewBarPrevious).unwrap(),
+                    KeyCode::Char(']') => tx.send(InputEvent::OverviewBarNext).unwrap(),
+                    KeyCode::Enter => tx.send(InputEvent::OverviewJump).unwrap(),
+
+                    // Playback groove
+                    KeyCode::Char('S') => tx.send(InputEvent::GrooveSwingNext).unwrap(),
+                    KeyCode::Char('D') => tx.send(InputEvent::GrooveSwingUnitToggle).unwrap(),
+                    KeyCode::Char('F') => tx.send(InputEvent::GrooveTemplateNext).unwrap(),
+                    KeyCode::Char('A') => tx.send(InputEvent::GrooveTrackSwingNext).unwrap(),
+
+                    // Selection transforms and history
+                    KeyCode::Char('y') => tx.send(InputEvent::Transform(Transform::Transpose(1))).unwrap(),
+                    KeyCode::Char('h') => tx.send(InputEvent::Transform(Transform::Transpose(-1))).unwrap(),
+                    KeyCode::Char('Y') => tx.send(InputEvent::Transform(Transform::Transpose(12))).unwrap(),
+                    KeyCode::Char('H') => tx.send(InputEvent::Transform(Transform::Transpose(-12))).unwrap(),
+                    KeyCode::Char('j') => tx.send(InputEvent::Transform(Transform::TransposeDiatonic(1))).unwrap(),
+                    KeyCode::Char('J') => tx.send(InputEvent::Transform(Transform::TransposeDiatonic(-1))).unwrap(),
+                    KeyCode::Char('n') => tx.send(InputEvent::Transform(Transform::Invert)).unwrap(),
+                    KeyCode::Char('N') => tx.send(InputEvent::Transform(Transform::Retrograde)).unwrap(),
+                    KeyCode::Char('9') => tx.send(InputEvent::Transform(Transform::Stretch(1, 2))).unwrap(),
+                    KeyCode::Char('0') => tx.send(InputEvent::Transform(Transform::Stretch(2, 1))).unwrap(),
+                    KeyCode::Char('Q') => tx.send(InputEvent::Quantize).unwrap(),
+                    KeyCode::Char('W') => tx.send(InputEvent::QuantizeTargetNext).unwrap(),
+                    KeyCode::Char('E') => tx.send(InputEvent::QuantizeStrengthNext).unwrap(),
+                    KeyCode::Char('R') => tx.send(InputEvent::QuantizeSwingNext).unwrap(),
+                    KeyCode::Char('T') => tx.send(InputEvent::Humanize).unwrap(),
+                    KeyCode::Char('q') => tx.send(InputEvent::Undo).unwrap(),
+                    KeyCode::Char('w') => tx.send(InputEvent::Redo).unwrap(),
 
                     KeyCode::Char('p') => {
                         tx.send(InputEvent::Quit).unwrap();
@@ -104,12 +420,22 @@ pub fn capture_input(tx: &mpsc::Sender<InputEvent>) -> io::Result<()> {
 
                     // Playback control
                     KeyCode::Char('\\') => tx.send(InputEvent::PlayerTogglePlayback).unwrap(),
+                    KeyCode::F(6) => tx.send(InputEvent::PlayFromCursor).unwrap(),
+                    KeyCode::F(7) => tx.send(InputEvent::PlayFromViewport).unwrap(),
+                    KeyCode::F(8) => tx.send(InputEvent::PlaySelection).unwrap(),
+                    KeyCode::F(9) => tx.send(InputEvent::ReturnOnStopToggle).unwrap(),
+                    KeyCode::F(10) => tx.send(InputEvent::FollowModeNext).unwrap(),
+                    KeyCode::F(12) => tx.send(InputEvent::SongEndAtCursor).unwrap(),
 
                     _ => (),
                 }
             }
         }
     }
+    if keys_mode.is_some_and(|held_keys| held_keys.key_release()) {
+        execute!(io::stdout(), PopKeyboardEnhancementFlags)?;
+    }
+    execute!(io::stdout(), DisableBracketedPaste)?;
     crossterm::terminal::disable_raw_mode()?;
     Ok(())
 }
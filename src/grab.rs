@@ -0,0 +1,177 @@
+use crate::score::{Note, Score};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrabHandle {
+    Body,  // Move notes in time and pitch
+    Start, // Move onsets, keeping ends
+    End,   // Move ends, keeping onsets
+}
+
+impl GrabHandle {
+    pub fn next(&self) -> GrabHandle {
+        match self {
+            GrabHandle::Body => GrabHandle::End,
+            GrabHandle::End => GrabHandle::Start,
+            GrabHandle::Start => GrabHandle::Body,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            GrabHandle::Body => "body",
+            GrabHandle::Start => "start",
+            GrabHandle::End => "end",
+        }
+    }
+}
+
+// Notes lifted out of the score while they are moved or resized. Offsets are applied
+// to the original notes, so a move that would be invalid for any note is refused.
+#[derive(Debug, Clone)]
+pub struct Grab {
+    notes: Vec<Note>,
+    time_offset: i64,
+    pitch_offset: i32,
+    start_offset: i64,
+    end_offset: i64,
+    handle: GrabHandle,
+}
+
+impl Grab {
+    pub fn new(notes: Vec<Note>) -> Grab {
+        Grab {
+            notes,
+            time_offset: 0,
+            pitch_offset: 0,
+            start_offset: 0,
+            end_offset: 0,
+            handle: GrabHandle::Body,
+        }
+    }
+
+    pub fn handle(&self) -> GrabHandle {
+        self.handle
+    }
+
+    pub fn next_handle(&self) -> Grab {
+        let mut grab = self.clone();
+        grab.handle = self.handle.next();
+        grab
+    }
+
+    pub fn shift_time(&self, delta_b32: i64) -> Grab {
+        let mut grab = self.clone();
+        match self.handle {
+            GrabHandle::Body => grab.time_offset += delta_b32,
+            GrabHandle::Start => grab.start_offset += delta_b32,
+            GrabHandle::End => grab.end_offset += delta_b32,
+        }
+        grab.validated(self)
+    }
+
+    pub fn shift_pitch(&self, semitones: i32) -> Grab {
+        let mut grab = self.clone();
+        grab.pitch_offset += semitones;
+        grab.validated(self)
+    }
+
+    // The grabbed notes at their current position and length.
+    pub fn moved_notes(&self) -> Vec<Note> {
+        self.notes.iter().filter_map(|note| self.move_note(*note)).collect()
+    }
+
+    // Score holding only the moved notes, for drawing them over the score.
+    pub fn preview_score(&self) -> Score {
+        let mut preview = Score::new(120);
+        for note in self.moved_notes() {
+            preview.insert_note(note);
+        }
+        preview
+    }
+
+    fn validated(self, previous: &Grab) -> Grab {
+        if self.moved_notes().len() == self.notes.len() {
+            self
+        } else {
+            previous.clone()
+        }
+    }
+
+    fn move_note(&self, note: Note) -> Option<Note> {
+        let onset = note.onset_b32 as i64 + self.time_offset + self.start_offset;
+        let end = (note.onset_b32 + note.duration_b32) as i64 + self.time_offset + self.end_offset;
+        if onset < 0 || end <= onset {
+            return None;
+        }
+        Some(Note {
+            pitch: note.pitch.transpose(self.pitch_offset)?,
+            onset_b32: onset as u64,
+            duration_b32: (end - onset) as u64,
+            velocity: note.velocity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::{Pitch, Tone};
+    use crate::score::note;
+    use crate::selection_range::{SelectionMode, SelectionRange};
+
+    #[test]
+    fn test_move_and_resize() {
+        let grab = Grab::new(vec![note(Pitch::new(Tone::E, 4), 32, 32)])
+            .shift_time(-8)
+            .shift_pitch(-4)
+            .next_handle()
+            .shift_time(4);
+        assert_eq!(grab.handle(), GrabHandle::End);
+        let moved = grab.moved_notes()[0];
+        assert_eq!((moved.pitch, moved.onset_b32, moved.duration_b32), (Pitch::new(Tone::C, 4), 24, 36));
+
+        // The start handle moves the onset and keeps the end
+        let moved = grab.next_handle().shift_time(8).moved_notes()[0];
+        assert_eq!((moved.onset_b32, moved.duration_b32), (32, 28));
+        assert_eq!(grab.next_handle().next_handle().handle(), GrabHandle::Body);
+    }
+
+    #[test]
+    fn test_invalid_moves_refused() {
+        let grab = Grab::new(vec![note(Pitch::new(Tone::C, 4), 8, 8), note(Pitch::new(Tone::E, 4), 32, 8)]);
+        // Before the start of the song for one of the notes, refused for both
+        assert_eq!(grab.shift_time(-16).moved_notes()[1].onset_b32, 32);
+        // An end at or before the onset is refused
+        let end_handle = grab.next_handle();
+        assert_eq!(end_handle.shift_time(-8).moved_notes()[0].duration_b32, 8);
+        assert_eq!(end_handle.shift_time(-4).moved_notes()[0].duration_b32, 4);
+        // Out of the pitch range
+        let high = Grab::new(vec![note(Pitch::new(Tone::B, 8), 0, 8)]);
+        assert_eq!(high.shift_pitch(1).moved_notes()[0].pitch, Pitch::new(Tone::B, 8));
+    }
+
+    #[test]
+    fn test_take_and_drop() {
+        let mut score = Score::new(120);
+        score.insert_note(note(Pitch::new(Tone::C, 4), 0, 32));
+        score.insert_note(note(Pitch::new(Tone::E, 4), 32, 32));
+        let range = SelectionRange {
+            time_point_start_b32: 32,
+            time_point_end_b32: 33,
+            pitch_low: Pitch::new(Tone::E, 4),
+            pitch_high: Pitch::new(Tone::E, 4),
+            mode: SelectionMode::Onset,
+        };
+
+        let grab = Grab::new(score.take_selection(range)).shift_time(-8).shift_pitch(-4);
+        assert!(score.notes_starting_at_time(32).is_empty());
+        assert_eq!(grab.preview_score().notes_starting_at_time(24).len(), 1);
+
+        // Dropping onto C4 merges with the existing note
+        for note in grab.moved_notes() {
+            score.insert_note(note);
+        }
+        let merged = score.note_at(Pitch::new(Tone::C, 4), 0).unwrap();
+        assert_eq!(merged.duration_b32, 56);
+    }
+}
@@ -0,0 +1,188 @@
+const SWING_MAX: u8 = 50;
+const SWING_STEP: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwingUnit {
+    Eighth,
+    Sixteenth,
+}
+
+impl SwingUnit {
+    pub fn grid_b32(&self) -> u64 {
+        match self {
+            SwingUnit::Eighth => 4,
+            SwingUnit::Sixteenth => 2,
+        }
+    }
+
+    pub fn toggle(&self) -> SwingUnit {
+        match self {
+            SwingUnit::Eighth => SwingUnit::Sixteenth,
+            SwingUnit::Sixteenth => SwingUnit::Eighth,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SwingUnit::Eighth => "1/8",
+            SwingUnit::Sixteenth => "1/16",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SwingUnit> {
+        match name {
+            "1/8" => Some(SwingUnit::Eighth),
+            "1/16" => Some(SwingUnit::Sixteenth),
+            _ => None,
+        }
+    }
+}
+
+// Fixed per-position delays for each 16th note of a bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrooveTemplate {
+    Straight,
+    LaidBack,
+    HeavyOffbeats,
+    Stagger,
+}
+
+impl GrooveTemplate {
+    // Delay of each 16th position in a bar, in percent of a 16th note.
+    pub fn offsets(&self) -> [u64; 16] {
+        match self {
+            GrooveTemplate::Straight => [0; 16],
+            GrooveTemplate::LaidBack => [0, 0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0, 20, 0, 0, 0],
+            GrooveTemplate::HeavyOffbeats => [0, 0, 15, 0, 0, 0, 15, 0, 0, 0, 15, 0, 0, 0, 15, 0],
+            GrooveTemplate::Stagger => [0, 10, 0, 20, 0, 10, 0, 20, 0, 10, 0, 20, 0, 10, 0, 20],
+        }
+    }
+
+    pub fn next(&self) -> GrooveTemplate {
+        match self {
+            GrooveTemplate::Straight => GrooveTemplate::LaidBack,
+            GrooveTemplate::LaidBack => GrooveTemplate::HeavyOffbeats,
+            GrooveTemplate::HeavyOffbeats => GrooveTemplate::Stagger,
+            GrooveTemplate::Stagger => GrooveTemplate::Straight,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            GrooveTemplate::Straight => "straight",
+            GrooveTemplate::LaidBack => "laid_back",
+            GrooveTemplate::HeavyOffbeats => "heavy_offbeats",
+            GrooveTemplate::Stagger => "stagger",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GrooveTemplate> {
+        match name {
+            "straight" => Some(GrooveTemplate::Straight),
+            "laid_back" => Some(GrooveTemplate::LaidBack),
+            "heavy_offbeats" => Some(GrooveTemplate::HeavyOffbeats),
+            "stagger" => Some(GrooveTemplate::Stagger),
+            _ => None,
+        }
+    }
+}
+
+// Playback timing feel. Notes keep their positions in the score; the player delays
+// their onsets. Templates only delay, since the player does not look ahead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Groove {
+    pub swing_unit: SwingUnit,
+    pub swing_percent: u8, // Delay of every second swing unit, as part of a unit
+    pub template: GrooveTemplate,
+    pub track_swing: Vec<Option<u8>>, // Per-track override of swing_percent
+}
+
+impl Groove {
+    pub fn new() -> Groove {
+        Groove {
+            swing_unit: SwingUnit::Sixteenth,
+            swing_percent: 0,
+            template: GrooveTemplate::Straight,
+            track_swing: Vec::new(),
+        }
+    }
+
+    pub fn track_swing_percent(&self, track: usize) -> u8 {
+        self.track_swing
+            .get(track)
+            .copied()
+            .flatten()
+            .unwrap_or(self.swing_percent)
+    }
+
+    pub fn next_swing(&self) -> Groove {
+        let mut groove = self.clone();
+        groove.swing_percent = next_swing_percent(self.swing_percent);
+        groove
+    }
+
+    pub fn toggle_swing_unit(&self) -> Groove {
+        let mut groove = self.clone();
+        groove.swing_unit = self.swing_unit.toggle();
+        groove
+    }
+
+    pub fn next_template(&self) -> Groove {
+        let mut groove = self.clone();
+        groove.template = self.template.next();
+        groove
+    }
+
+    // Cycles the track through following the global swing and its own amounts.
+    pub fn next_track_swing(&self, track: usize) -> Groove {
+        let mut groove = self.clone();
+        if groove.track_swing.len() <= track {
+            groove.track_swing.resize(track + 1, None);
+        }
+        groove.track_swing[track] = match groove.track_swing[track] {
+            None => Some(0),
+            Some(SWING_MAX) => None,
+            Some(percent) => Some(next_swing_percent(percent)),
+        };
+        groove
+    }
+
+    pub fn set_track_swing(&self, track: usize, percent: Option<u8>) -> Groove {
+        let mut groove = self.clone();
+        if groove.track_swing.len() <= track {
+            groove.track_swing.resize(track + 1, None);
+        }
+        groove.track_swing[track] = percent.map(|percent| percent.min(SWING_MAX));
+        groove
+    }
+
+    // Delay of a note starting at `onset_b32`, in hundredths of a b32.
+    pub fn delay_b32_hundredths(&self, onset_b32: u64, track: usize) -> u64 {
+        let mut delay = 0;
+
+        let unit = self.swing_unit.grid_b32();
+        if onset_b32 % (2 * unit) == unit {
+            delay += unit * self.track_swing_percent(track) as u64;
+        }
+
+        if onset_b32.is_multiple_of(2) {
+            let position = (onset_b32 % 32 / 2) as usize;
+            delay += 2 * self.template.offsets()[position];
+        }
+        delay
+    }
+}
+
+impl Default for Groove {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn next_swing_percent(percent: u8) -> u8 {
+    if percent >= SWING_MAX {
+        0
+    } else {
+        (percent + SWING_STEP).min(SWING_MAX)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::loop_state::LoopState;
+use crate::score::Score;
+
+const HISTORY_LIMIT: usize = 100;
+
+// A score snapshot. Edits that move the loop along with the notes keep it too,
+// so undo and redo put it back; other edits leave the loop alone.
+pub struct Snapshot {
+    pub score: Score,
+    pub loop_state: Option<LoopState>,
+}
+
+// Snapshots of the score taken before each edit.
+pub struct History {
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, score: &Score, loop_state: Option<LoopState>) {
+        if self.undo_stack.len() == HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(Snapshot {
+            score: score.clone(),
+            loop_state,
+        });
+        self.redo_stack.clear();
+    }
+
+    // Drops the last snapshot and returns it, for an edit that was called off.
+    pub fn take_back(&mut self) -> Option<Snapshot> {
+        self.undo_stack.pop()
+    }
+
+    pub fn undo(&mut self, current: &Score, current_loop_state: LoopState) -> Option<Snapshot> {
+        let previous = self.undo_stack.pop()?;
+        self.redo_stack.push(Snapshot {
+            score: current.clone(),
+            loop_state: previous.loop_state.map(|_| current_loop_state),
+        });
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: &Score, current_loop_state: LoopState) -> Option<Snapshot> {
+        let next = self.redo_stack.pop()?;
+        self.undo_stack.push(Snapshot {
+            score: current.clone(),
+            loop_state: next.loop_state.map(|_| current_loop_state),
+        });
+        Some(next)
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::{Pitch, Tone};
+
+    #[test]
+    fn test_undo_insert_bar_restores_loop() {
+        let mut score = Score::new(120);
+        score.insert(Pitch::new(Tone::C, 4), 64, 8);
+        let loop_state = LoopState::new().set_region(64, 96);
+        let mut history = History::new();
+
+        history.record(&score, Some(loop_state));
+        score.insert_time(32, 32);
+        let moved_loop_state = loop_state.insert_time(32, 32);
+        assert_eq!(moved_loop_state.region(), Some((96, 128)));
+
+        let snapshot = history.undo(&score, moved_loop_state).unwrap();
+        assert_eq!(snapshot.score.notes_starting_at_time(64).len(), 1);
+        assert_eq!(snapshot.loop_state.unwrap().region(), Some((64, 96)));
+        let snapshot = history.redo(&snapshot.score, loop_state).unwrap();
+        assert_eq!(snapshot.loop_state.unwrap().region(), Some((96, 128)));
+
+        // Note edits don't touch the loop, which may have moved since
+        history.record(&score, None);
+        let snapshot = history.undo(&score, LoopState::new()).unwrap();
+        assert!(snapshot.loop_state.is_none());
+    }
+}
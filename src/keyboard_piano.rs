@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+// Length of a note when the terminal can't report key release. Auto-repeat of a held key
+// keeps extending it.
+const FALLBACK_NOTE_DURATION: Duration = Duration::from_millis(300);
+
+// Two rows of the QWERTY keyboard as a chromatic keyboard, tracker style. The bottom row
+// from 'z' plays the octave with its black keys on the row above, and the top row from
+// 'q' plays the octave above, with black keys on the number row.
+pub fn key_semitone(c: char) -> Option<u8> {
+    const LOWER: &str = "zsxdcvgbhnjm,";
+    const UPPER: &str = "q2w3er5t6y7ui";
+    if let Some(semitone) = LOWER.find(c) {
+        return Some(semitone as u8);
+    }
+    UPPER.find(c).map(|semitone| semitone as u8 + 12)
+}
+
+// The piano keys held down, by semitone above the keys octave.
+pub struct HeldKeys {
+    held: Vec<(u8, Instant)>,
+    key_release: bool, // The terminal reports key release
+}
+
+impl HeldKeys {
+    pub fn new(key_release: bool) -> HeldKeys {
+        HeldKeys {
+            held: Vec::new(),
+            key_release,
+        }
+    }
+
+    pub fn key_release(&self) -> bool {
+        self.key_release
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    // True if the press starts a note, false for a key that is already down.
+    pub fn press(&mut self, semitone: u8, now: Instant) -> bool {
+        let release_at = now + FALLBACK_NOTE_DURATION;
+        match self.held.iter_mut().find(|(held, _)| *held == semitone) {
+            Some((_, held_release_at)) => {
+                *held_release_at = release_at;
+                false
+            }
+            None => {
+                self.held.push((semitone, release_at));
+                true
+            }
+        }
+    }
+
+    // True if the key was down.
+    pub fn release(&mut self, semitone: u8) -> bool {
+        let count = self.held.len();
+        self.held.retain(|(held, _)| *held != semitone);
+        self.held.len() != count
+    }
+
+    // Keys whose fixed length note has run out, when release isn't reported.
+    pub fn expire(&mut self, now: Instant) -> Vec<u8> {
+        if self.key_release {
+            return Vec::new();
+        }
+        let expired = self.held.iter().filter(|(_, release_at)| *release_at <= now).map(|(semitone, _)| *semitone).collect();
+        self.held.retain(|(_, release_at)| *release_at > now);
+        expired
+    }
+
+    pub fn release_all(&mut self) -> Vec<u8> {
+        self.held.drain(..).map(|(semitone, _)| semitone).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_semitone() {
+        assert_eq!(key_semitone('z'), Some(0));
+        assert_eq!(key_semitone('s'), Some(1));
+        assert_eq!(key_semitone(','), Some(12));
+        assert_eq!(key_semitone('q'), Some(12));
+        assert_eq!(key_semitone('i'), Some(24));
+        assert_eq!(key_semitone('a'), None);
+    }
+
+    #[test]
+    fn test_held_keys() {
+        // Without key release, notes end after a fixed time that auto-repeat extends
+        let start = Instant::now();
+        let mut held_keys = HeldKeys::new(false);
+        assert!(held_keys.press(0, start));
+        assert!(held_keys.press(4, start));
+        assert!(!held_keys.press(0, start + Duration::from_millis(200)));
+        assert_eq!(held_keys.expire(start + Duration::from_millis(400)), vec![4]);
+        assert_eq!(held_keys.expire(start + Duration::from_millis(600)), vec![0]);
+        assert!(held_keys.is_empty());
+
+        let mut held_keys = HeldKeys::new(true);
+        held_keys.press(7, start);
+        assert!(held_keys.expire(start + Duration::from_secs(5)).is_empty());
+        assert!(held_keys.release(7));
+        assert!(!held_keys.release(7));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::ripple;
+use crate::score::BAR_B32;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LoopMode {
     Disabled,
@@ -36,15 +39,62 @@ impl LoopState {
                     new_state.end_time_b32 = Some(time_b32);
                 }
             }
-            (Some(_), Some(_)) => {
-                // If both times are set, clear and start over
-                new_state.start_time_b32 = Some(time_b32);
-                new_state.end_time_b32 = None;
+            (Some(start), Some(end)) => {
+                // With both set, move whichever point is nearer
+                new_state = if time_b32.abs_diff(start) <= time_b32.abs_diff(end) {
+                    self.set_region(time_b32, end)
+                } else {
+                    self.set_region(start, time_b32)
+                };
             }
         }
         new_state
     }
 
+    // Sets both points, in either order. An empty region clears the end point.
+    pub fn set_region(&self, a_b32: u64, b_b32: u64) -> Self {
+        let mut new_state = *self;
+        new_state.start_time_b32 = Some(a_b32.min(b_b32));
+        new_state.end_time_b32 = if a_b32 == b_b32 { None } else { Some(a_b32.max(b_b32)) };
+        new_state
+    }
+
+    // The start and end points when both are set.
+    pub fn region(&self) -> Option<(u64, u64)> {
+        Some((self.start_time_b32?, self.end_time_b32?))
+    }
+
+    // Moves the region by `delta_b32`, keeping its length. Stops at time 0.
+    pub fn nudge(&self, delta_b32: i64) -> Self {
+        let Some((start, end)) = self.region() else {
+            return *self;
+        };
+        let delta_b32 = delta_b32.max(-(start as i64));
+        self.set_region(start.saturating_add_signed(delta_b32), end.saturating_add_signed(delta_b32))
+    }
+
+    // Widens the region out to whole bars.
+    pub fn snap_to_bar(&self) -> Self {
+        let Some((start, end)) = self.region() else {
+            return *self;
+        };
+        self.set_region(start - start % BAR_B32, end.div_ceil(BAR_B32) * BAR_B32)
+    }
+
+    pub fn double(&self) -> Self {
+        let Some((start, end)) = self.region() else {
+            return *self;
+        };
+        self.set_region(start, start + (end - start) * 2)
+    }
+
+    pub fn halve(&self) -> Self {
+        let Some((start, end)) = self.region() else {
+            return *self;
+        };
+        self.set_region(start, start + ((end - start) / 2).max(1))
+    }
+
     pub fn set_mode(&self, mode: LoopMode) -> Self {
         let mut new_state = *self;
         new_state.mode = mode;
@@ -60,8 +110,23 @@ impl LoopState {
         new_state
     }
 
-    pub fn clear(&self) -> Self {
-        Self::new()
+    pub fn insert_time(&self, at_b32: u64, length_b32: u64) -> Self {
+        let mut new_state = *self;
+        new_state.start_time_b32 = self.start_time_b32.map(|t| ripple::after_insert(t, at_b32, length_b32));
+        new_state.end_time_b32 = self.end_time_b32.map(|t| ripple::after_insert(t, at_b32, length_b32));
+        new_state
+    }
+
+    // A region inside the deleted span collapses to a point, which clears the end point
+    // as for any empty region.
+    pub fn delete_time(&self, start_b32: u64, length_b32: u64) -> Self {
+        let mut new_state = *self;
+        new_state.start_time_b32 = self.start_time_b32.map(|t| ripple::after_delete(t, start_b32, length_b32));
+        new_state.end_time_b32 = self.end_time_b32.map(|t| ripple::after_delete(t, start_b32, length_b32));
+        match new_state.region() {
+            Some((start, end)) => new_state.set_region(start, end),
+            None => new_state,
+        }
     }
 
     pub fn is_looping(&self) -> bool {
@@ -76,3 +141,38 @@ impl Default for LoopState {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_region_editing() {
+        // A third mark moves the nearer point instead of starting over
+        let loop_state = LoopState::new().mark(32).mark(96).mark(40);
+        assert_eq!(loop_state.region(), Some((40, 96)));
+        assert_eq!(loop_state.mark(100).region(), Some((40, 100)));
+
+        assert_eq!(loop_state.nudge(-8).region(), Some((32, 88)));
+        assert_eq!(loop_state.nudge(-100).region(), Some((0, 56)));
+        assert_eq!(loop_state.snap_to_bar().region(), Some((32, 96)));
+        assert_eq!(loop_state.double().region(), Some((40, 152)));
+        assert_eq!(loop_state.halve().region(), Some((40, 68)));
+        assert_eq!(LoopState::new().set_region(64, 0).region(), Some((0, 64)));
+        assert_eq!(LoopState::new().double().region(), None);
+    }
+
+    #[test]
+    fn test_loop_ripple() {
+        let loop_state = LoopState::new().set_region(40, 72).set_mode(LoopMode::Looping);
+        assert_eq!(loop_state.insert_time(32, 32).region(), Some((72, 104)));
+        assert_eq!(loop_state.delete_time(0, 32).region(), Some((8, 40)));
+
+        // A loop inside the deleted bar doesn't stay active with no length
+        let loop_state = LoopState::new().set_region(40, 56).set_mode(LoopMode::Looping);
+        let loop_state = loop_state.delete_time(32, 32);
+        assert_eq!(loop_state.start_time_b32, Some(32));
+        assert_eq!(loop_state.region(), None);
+        assert!(!loop_state.is_looping());
+    }
+}
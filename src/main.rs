@@ -9,14 +9,33 @@ use std::{
 use std::env;
 use std::path::PathBuf;
 mod app_state;
+mod arrangement;
 mod audio;
 mod cursor;
 mod draw_components;
+mod event_log;
 mod events;
+mod grab;
+mod groove;
+mod history;
+mod keyboard_piano;
 mod loop_state;
+mod marker;
+mod metronome;
+mod midi_file;
+mod midi_input;
+mod midi_output;
+mod mixer;
+mod panel_view;
 mod pitch;
+mod osc52;
 mod player;
+mod quantize;
+mod recorder;
+mod registers;
 mod resolution;
+mod ripple;
+mod scale;
 mod score;
 mod score_viewport;
 mod selection_buffer;
@@ -24,10 +43,13 @@ mod selection_range;
 mod sin_wave;
 mod song;
 mod song_file;
+mod theme;
+mod transform;
+mod zoom;
 
 use app_state::AppState;
+use crate::loop_state::LoopState;
 use crate::score::Score;
-use std::collections::HashMap;
 use crate::song_file::SongFile;
 
 fn main() -> io::Result<()> {
@@ -41,12 +63,12 @@ fn main() -> io::Result<()> {
 
     info!("Application starting...");
 
-    let score = if let Some(path) = env::args().nth(1) {
+    let (score, loop_state) = if let Some(path) = env::args().nth(1) {
         info!("Loading song from {}", path);
         match SongFile::load(PathBuf::from(&path)) {
-            Ok(loaded_score) => {
+            Ok((loaded_score, loop_state)) => {
                 info!("Successfully loaded song from {}", path);
-                Arc::new(Mutex::new(loaded_score))
+                (Arc::new(Mutex::new(loaded_score)), loop_state)
             }
             Err(e) => {
                 eprintln!("Failed to load song from {}: {}", path, e);
@@ -55,14 +77,10 @@ fn main() -> io::Result<()> {
         }
     } else {
         info!("Starting with blank song");
-        Arc::new(Mutex::new(Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        }))
+        (Arc::new(Mutex::new(Score::new(120))), LoopState::new())
     };
     
-    let mut app_state = AppState::new(score);
+    let mut app_state = AppState::new(score, loop_state);
     app_state.run()?;
 
     Ok(())
@@ -0,0 +1,39 @@
+// Names offered for markers, in the usual order of a song's sections.
+const SECTION_NAMES: [&str; 7] = ["Intro", "Verse", "Pre-Chorus", "Chorus", "Bridge", "Solo", "Outro"];
+
+// A named point on the timeline. Each marker starts a section that runs to the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub time_b32: u64,
+    pub name: String,
+}
+
+impl Marker {
+    pub fn new(time_b32: u64, name: &str) -> Marker {
+        Marker {
+            time_b32,
+            name: name.to_string(),
+        }
+    }
+}
+
+// The section name after `name`, for renaming a marker by cycling through the list.
+// Names not in the list start it over.
+pub fn next_section_name(name: &str) -> &'static str {
+    match SECTION_NAMES.iter().position(|section_name| *section_name == name) {
+        Some(index) => SECTION_NAMES[(index + 1) % SECTION_NAMES.len()],
+        None => SECTION_NAMES[0],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_section_name() {
+        assert_eq!(next_section_name("Verse"), "Pre-Chorus");
+        assert_eq!(next_section_name("Outro"), "Intro");
+        assert_eq!(next_section_name("Breakdown"), "Intro");
+    }
+}
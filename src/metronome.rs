@@ -0,0 +1,145 @@
+use crate::score::BAR_B32;
+
+// The metronome clicks every quarter note and accents the first beat of each bar.
+const BEAT_B32: u64 = BAR_B32 / 4;
+const VOLUME_STEP: u8 = 25;
+const COUNT_IN_MAX_BARS: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetronomeMode {
+    Off,
+    On,
+    Recording, // Clicks only while recording
+}
+
+impl MetronomeMode {
+    pub fn next(&self) -> MetronomeMode {
+        match self {
+            MetronomeMode::Off => MetronomeMode::On,
+            MetronomeMode::On => MetronomeMode::Recording,
+            MetronomeMode::Recording => MetronomeMode::Off,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            MetronomeMode::Off => "off",
+            MetronomeMode::On => "on",
+            MetronomeMode::Recording => "rec only",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metronome {
+    pub mode: MetronomeMode,
+    pub volume: u8, // 0-100, separate from the track volume
+    pub count_in_bars: u64, // Clicked before playback, when the mode would click
+}
+
+impl Metronome {
+    pub fn new() -> Self {
+        Self {
+            mode: MetronomeMode::Off,
+            volume: 50,
+            count_in_bars: 0,
+        }
+    }
+
+    pub fn next_mode(&self) -> Self {
+        Self {
+            mode: self.mode.next(),
+            ..*self
+        }
+    }
+
+    // Steps the volume up, wrapping from full back to the lowest step.
+    pub fn next_volume(&self) -> Self {
+        Self {
+            volume: if self.volume >= 100 { VOLUME_STEP } else { self.volume + VOLUME_STEP },
+            ..*self
+        }
+    }
+
+    pub fn next_count_in(&self) -> Self {
+        Self {
+            count_in_bars: (self.count_in_bars + 1) % (COUNT_IN_MAX_BARS + 1),
+            ..*self
+        }
+    }
+
+    pub fn is_clicking(&self, recording: bool) -> bool {
+        match self.mode {
+            MetronomeMode::Off => false,
+            MetronomeMode::On => true,
+            MetronomeMode::Recording => recording,
+        }
+    }
+
+    // The count-in follows the mode: none while the metronome is off, and in rec only
+    // mode just before recording.
+    pub fn counts_in(&self, recording: bool) -> bool {
+        self.count_in_bars > 0 && self.is_clicking(recording)
+    }
+
+    // Whether a click falls at `time_b32`, and if it is accented.
+    pub fn click_at(time_b32: u64) -> Option<bool> {
+        if !time_b32.is_multiple_of(BEAT_B32) {
+            return None;
+        }
+        Some(time_b32.is_multiple_of(BAR_B32))
+    }
+
+    pub fn as_str(&self) -> String {
+        format!(
+            "Metronome: {} {}%  count-in {} bar(s)",
+            self.mode.as_str(),
+            self.volume,
+            self.count_in_bars
+        )
+    }
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_click_at() {
+        // Quarter note clicks, accented on the downbeat
+        assert_eq!(Metronome::click_at(0), Some(true));
+        assert_eq!(Metronome::click_at(8), Some(false));
+        assert_eq!(Metronome::click_at(12), None);
+        assert_eq!(Metronome::click_at(64), Some(true));
+    }
+
+    #[test]
+    fn test_metronome_settings() {
+        let metronome = Metronome::new().next_mode().next_mode();
+        assert_eq!(metronome.mode, MetronomeMode::Recording);
+        assert!(!metronome.is_clicking(false));
+        assert!(metronome.is_clicking(true));
+
+        let mut metronome = Metronome::new();
+        for _ in 0..5 {
+            metronome = metronome.next_count_in();
+        }
+        assert_eq!(metronome.count_in_bars, 0);
+        assert_eq!(metronome.next_count_in().count_in_bars, 1);
+        assert_eq!(metronome.next_volume().next_volume().volume, 100);
+        assert_eq!(metronome.next_volume().next_volume().next_volume().volume, 25);
+
+        // No count-in while the metronome is off, or before playback in rec only mode
+        let metronome = metronome.next_count_in();
+        assert!(!metronome.counts_in(true));
+        let metronome = metronome.next_mode().next_mode();
+        assert!(!metronome.counts_in(false));
+        assert!(metronome.counts_in(true));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::midi_output::MidiOutMessage;
+use crate::score::Score;
+
+// Ticks per quarter note. A quarter note is 8 b32 units, so each unit is 12 ticks.
+const TICKS_PER_QUARTER: u16 = 96;
+const TICKS_PER_B32: u64 = 12;
+
+// Order of events that share a time: notes end before markers, and markers come
+// before the notes they introduce.
+const ORDER_NOTE_OFF: u8 = 0;
+const ORDER_META: u8 = 1;
+const ORDER_NOTE_ON: u8 = 2;
+
+// The song as a format 0 Standard MIDI File: the tempo, a marker meta event at the
+// start of each section, then every note on channel 1.
+pub fn song_to_midi(score: &Score) -> Vec<u8> {
+    let mut events: Vec<(u64, u8, Vec<u8>)> = Vec::new();
+    let micros_per_quarter = (60_000_000 / score.bpm as u32).to_be_bytes();
+    events.push((0, ORDER_META, meta_event(0x51, &micros_per_quarter[1..])));
+    for marker in &score.markers {
+        events.push((marker.time_b32, ORDER_META, meta_event(0x06, marker.name.as_bytes())));
+    }
+    for note in score.song_notes() {
+        let note_on = MidiOutMessage::NoteOn(0, note.pitch, note.velocity);
+        let note_off = MidiOutMessage::NoteOff(0, note.pitch);
+        events.push((note.onset_b32, ORDER_NOTE_ON, note_on.bytes()));
+        events.push((note.onset_b32 + note.duration_b32, ORDER_NOTE_OFF, note_off.bytes()));
+    }
+    events.sort_by_key(|(time_b32, order, _)| (*time_b32, *order));
+
+    let mut track = Vec::new();
+    let mut last_time_b32 = 0;
+    for (time_b32, _, bytes) in events {
+        write_variable_length(&mut track, (time_b32 - last_time_b32) * TICKS_PER_B32);
+        track.extend(bytes);
+        last_time_b32 = time_b32;
+    }
+    // End of track at the song end, keeping trailing rests
+    let end_b32 = score.song_length_b32().max(last_time_b32);
+    write_variable_length(&mut track, (end_b32 - last_time_b32) * TICKS_PER_B32);
+    track.extend([0xFF, 0x2F, 0x00]);
+
+    let mut file = Vec::new();
+    file.extend(b"MThd");
+    file.extend(6u32.to_be_bytes());
+    file.extend(0u16.to_be_bytes()); // Format 0, a single track
+    file.extend(1u16.to_be_bytes());
+    file.extend(TICKS_PER_QUARTER.to_be_bytes());
+    file.extend(b"MTrk");
+    file.extend((track.len() as u32).to_be_bytes());
+    file.extend(track);
+    file
+}
+
+fn meta_event(meta_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0xFF, meta_type];
+    write_variable_length(&mut bytes, data.len() as u64);
+    bytes.extend(data);
+    bytes
+}
+
+// Seven bits per byte, most significant first, with the top bit set on all but the last.
+fn write_variable_length(out: &mut Vec<u8>, value: u64) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::{Pitch, Tone};
+    use crate::score::{Note, DEFAULT_VELOCITY};
+
+    #[test]
+    fn test_song_to_midi() {
+        let mut score = Score::new(120);
+        score.insert_note(Note {
+            pitch: Pitch::new(Tone::C, 4),
+            onset_b32: 0,
+            duration_b32: 32,
+            velocity: DEFAULT_VELOCITY,
+        });
+        score.set_marker(32, "Pre-Chorus");
+
+        // Marker meta events sit in the MIDI file's single track
+        let midi = song_to_midi(&score);
+        assert_eq!(&midi[..4], b"MThd");
+        let marker_event = [0xFF, 0x06, 10, b'P', b'r', b'e'];
+        assert!(midi.windows(marker_event.len()).any(|window| window == marker_event));
+        // The track ends at the song end, a bar after the note
+        let end_of_track = [0x83, 0x00, 0xFF, 0x2F, 0x00];
+        assert_eq!(&midi[midi.len() - end_of_track.len()..], &end_of_track);
+    }
+}
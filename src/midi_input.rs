@@ -0,0 +1,212 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::events::InputEvent;
+use crate::pitch::Pitch;
+
+// Path of a file or FIFO to read raw MIDI bytes from instead of the ALSA sequencer,
+// e.g. `mkfifo /tmp/midi` then `printf '\x90\x3c\x64' > /tmp/midi`.
+const LOOPBACK_ENV: &str = "TIMELINE_MIDI_LOOPBACK";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    NoteOn(Pitch, u8),
+    NoteOff(Pitch),
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    // Note on with velocity 0 is a note off, as many keyboards send it.
+    fn from_note(note_on: bool, note: u8, velocity: u8) -> Option<MidiMessage> {
+        let pitch = Pitch::from_midi(note)?;
+        if note_on && velocity > 0 {
+            Some(MidiMessage::NoteOn(pitch, velocity))
+        } else {
+            Some(MidiMessage::NoteOff(pitch))
+        }
+    }
+
+    fn input_event(self) -> InputEvent {
+        match self {
+            MidiMessage::NoteOn(pitch, velocity) => InputEvent::MidiNoteOn(pitch, velocity),
+            MidiMessage::NoteOff(pitch) => InputEvent::MidiNoteOff(pitch),
+            MidiMessage::Clock => InputEvent::MidiClock,
+            MidiMessage::Start => InputEvent::MidiStart,
+            MidiMessage::Continue => InputEvent::MidiContinue,
+            MidiMessage::Stop => InputEvent::MidiStop,
+        }
+    }
+}
+
+// Turns a raw MIDI byte stream into note and clock messages, with notes on any channel.
+// Running status is kept, other messages are skipped.
+pub struct MidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl MidiParser {
+    pub fn new() -> MidiParser {
+        MidiParser {
+            status: None,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Real-time bytes may arrive between the bytes of any message
+        match byte {
+            0xF8 => return Some(MidiMessage::Clock),
+            0xFA => return Some(MidiMessage::Start),
+            0xFB => return Some(MidiMessage::Continue),
+            0xFC => return Some(MidiMessage::Stop),
+            0xF9..=0xFF => return None,
+            _ => (),
+        }
+        if byte & 0x80 != 0 {
+            // System common messages cancel running status
+            self.status = if byte < 0xF0 { Some(byte) } else { None };
+            self.data.clear();
+            return None;
+        }
+
+        let status = self.status?;
+        self.data.push(byte);
+        let data_len = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        if self.data.len() < data_len {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data);
+        match status & 0xF0 {
+            0x90 => MidiMessage::from_note(true, data[0], data[1]),
+            0x80 => MidiMessage::from_note(false, data[0], data[1]),
+            _ => None,
+        }
+    }
+}
+
+// Starts reading MIDI input on its own thread, sending notes as input events. Returns a
+// description of the source.
+pub fn spawn(tx: mpsc::Sender<InputEvent>) -> io::Result<String> {
+    match env::var(LOOPBACK_ENV) {
+        Ok(path) => {
+            let source = format!("loopback {}", path);
+            thread::spawn(move || read_loopback(&path, &tx));
+            Ok(source)
+        }
+        Err(_) => spawn_sequencer(tx),
+    }
+}
+
+fn read_loopback(path: &str, tx: &mpsc::Sender<InputEvent>) {
+    let mut parser = MidiParser::new();
+    // A FIFO reports end of file when the writer closes, so open it again
+    while let Ok(file) = File::open(path) {
+        for byte in BufReader::new(file).bytes() {
+            let Ok(byte) = byte else {
+                break;
+            };
+            if let Some(message) = parser.push(byte) {
+                if tx.send(message.input_event()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Opens an ALSA sequencer port that keyboards and other programs can connect to.
+#[cfg(target_os = "linux")]
+fn spawn_sequencer(tx: mpsc::Sender<InputEvent>) -> io::Result<String> {
+    use alsa::seq::{EvNote, EventType, PortCap, PortType, Seq};
+    use std::ffi::CString;
+
+    let (ready_tx, ready_rx) = mpsc::channel();
+    // The sequencer handle can't move between threads, so it is opened on the reader
+    thread::spawn(move || {
+        let opened = Seq::open(None, Some(alsa::Direction::Capture), false).and_then(|seq| {
+            seq.set_client_name(&CString::new("timeline").unwrap())?;
+            let port = seq.create_simple_port(
+                &CString::new("in").unwrap(),
+                PortCap::WRITE | PortCap::SUBS_WRITE,
+                PortType::MIDI_GENERIC | PortType::APPLICATION,
+            )?;
+            Ok((seq, port))
+        });
+        let seq = match opened {
+            Ok((seq, port)) => {
+                let _ = ready_tx.send(Ok(format!("ALSA sequencer {}:{}", seq.client_id().unwrap_or(0), port)));
+                seq
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(io::Error::other(e)));
+                return;
+            }
+        };
+
+        let mut input = seq.input();
+        while let Ok(event) = input.event_input() {
+            let message = match event.get_type() {
+                EventType::Noteon | EventType::Noteoff => event.get_data::<EvNote>().and_then(|note| {
+                    MidiMessage::from_note(event.get_type() == EventType::Noteon, note.note, note.velocity)
+                }),
+                EventType::Clock => Some(MidiMessage::Clock),
+                EventType::Start => Some(MidiMessage::Start),
+                EventType::Continue => Some(MidiMessage::Continue),
+                EventType::Stop => Some(MidiMessage::Stop),
+                _ => None,
+            };
+            if let Some(message) = message {
+                if tx.send(message.input_event()).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    ready_rx.recv().map_err(io::Error::other)?
+}
+
+#[cfg(not(target_os = "linux"))]
+fn spawn_sequencer(_tx: mpsc::Sender<InputEvent>) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("no MIDI input on this platform, set {} to read raw MIDI from a file", LOOPBACK_ENV),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::Tone;
+
+    #[test]
+    fn test_midi_parser() {
+        let mut parser = MidiParser::new();
+        // Note on C4, running status note on E4 with a clock byte in between, then E4 off by velocity 0
+        let messages: Vec<MidiMessage> = [0x90, 60, 100, 64, 0xF8, 90, 0x80, 60, 0, 0x91, 64, 0]
+            .into_iter()
+            .filter_map(|byte| parser.push(byte))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::NoteOn(Pitch::new(Tone::C, 4), 100),
+                MidiMessage::Clock,
+                MidiMessage::NoteOn(Pitch::new(Tone::E, 4), 90),
+                MidiMessage::NoteOff(Pitch::new(Tone::C, 4)),
+                MidiMessage::NoteOff(Pitch::new(Tone::E, 4)),
+            ]
+        );
+        assert_eq!(Pitch::from_midi(69), Some(Pitch::new(Tone::A, 4)));
+        assert_eq!(Pitch::from_midi(11), None);
+    }
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::pitch::Pitch;
+
+// Path of a file or FIFO to write raw MIDI bytes to instead of the ALSA sequencer.
+const LOOPBACK_ENV: &str = "TIMELINE_MIDI_OUT_LOOPBACK";
+
+// MIDI clock runs at 24 pulses per quarter note, which is 3 per b32 step.
+pub const CLOCKS_PER_B32: u64 = 3;
+
+// Where the tempo comes from, and whether it is passed on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockSync {
+    Internal, // Own tempo, no clock out
+    Send,     // Own tempo, sends clock and start/stop/continue
+    External, // Follows incoming MIDI clock
+}
+
+impl ClockSync {
+    pub fn next(&self) -> ClockSync {
+        match self {
+            ClockSync::Internal => ClockSync::Send,
+            ClockSync::Send => ClockSync::External,
+            ClockSync::External => ClockSync::Internal,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ClockSync::Internal => "internal",
+            ClockSync::Send => "send",
+            ClockSync::External => "external",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiOutMessage {
+    NoteOn(u8, Pitch, u8), // Channel 0-15, pitch, velocity
+    NoteOff(u8, Pitch),
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiOutMessage {
+    pub fn bytes(&self) -> Vec<u8> {
+        match *self {
+            MidiOutMessage::NoteOn(channel, pitch, velocity) => vec![0x90 | channel, pitch.midi_note(), velocity],
+            MidiOutMessage::NoteOff(channel, pitch) => vec![0x80 | channel, pitch.midi_note(), 0],
+            MidiOutMessage::Clock => vec![0xF8],
+            MidiOutMessage::Start => vec![0xFA],
+            MidiOutMessage::Continue => vec![0xFB],
+            MidiOutMessage::Stop => vec![0xFC],
+        }
+    }
+}
+
+// Starts a thread that sends messages to the MIDI output. Returns the sender and a
+// description of the destination.
+pub fn spawn() -> io::Result<(mpsc::Sender<MidiOutMessage>, String)> {
+    match env::var(LOOPBACK_ENV) {
+        Ok(path) => {
+            let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
+            let (tx, rx) = mpsc::channel::<MidiOutMessage>();
+            thread::spawn(move || {
+                for message in rx {
+                    if file.write_all(&message.bytes()).and_then(|_| file.flush()).is_err() {
+                        return;
+                    }
+                }
+            });
+            Ok((tx, format!("loopback {}", path)))
+        }
+        Err(_) => spawn_sequencer(),
+    }
+}
+
+// Opens an ALSA sequencer port that synths can subscribe to.
+#[cfg(target_os = "linux")]
+fn spawn_sequencer() -> io::Result<(mpsc::Sender<MidiOutMessage>, String)> {
+    use alsa::seq::{MidiEvent, PortCap, PortType, Seq};
+    use std::ffi::CString;
+
+    let (tx, rx) = mpsc::channel::<MidiOutMessage>();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    // The sequencer handle can't move between threads, so it is opened on the writer
+    thread::spawn(move || {
+        let opened = Seq::open(None, Some(alsa::Direction::Playback), false).and_then(|seq| {
+            seq.set_client_name(&CString::new("timeline").unwrap())?;
+            let port = seq.create_simple_port(
+                &CString::new("out").unwrap(),
+                PortCap::READ | PortCap::SUBS_READ,
+                PortType::MIDI_GENERIC | PortType::APPLICATION,
+            )?;
+            let encoder = MidiEvent::new(16)?;
+            Ok((seq, port, encoder))
+        });
+        let (seq, port, mut encoder) = match opened {
+            Ok((seq, port, encoder)) => {
+                let _ = ready_tx.send(Ok(format!("ALSA sequencer {}:{}", seq.client_id().unwrap_or(0), port)));
+                (seq, port, encoder)
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(io::Error::other(e)));
+                return;
+            }
+        };
+
+        for message in rx {
+            let bytes = message.bytes();
+            if let Ok((_, Some(mut event))) = encoder.encode(&bytes) {
+                event.set_source(port);
+                event.set_subs();
+                event.set_direct();
+                let _ = seq.event_output_direct(&mut event);
+            }
+        }
+    });
+    let source = ready_rx.recv().map_err(io::Error::other)??;
+    Ok((tx, source))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn spawn_sequencer() -> io::Result<(mpsc::Sender<MidiOutMessage>, String)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("no MIDI output on this platform, set {} to write raw MIDI to a file", LOOPBACK_ENV),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::Tone;
+
+    #[test]
+    fn test_midi_output_messages() {
+        let pitch = Pitch::new(Tone::A, 4);
+        assert_eq!(Pitch::from_midi(pitch.midi_note()), Some(pitch));
+        assert_eq!(MidiOutMessage::NoteOn(2, pitch, 100).bytes(), vec![0x92, 69, 100]);
+        assert_eq!(MidiOutMessage::NoteOff(15, pitch).bytes(), vec![0x8F, 69, 0]);
+        assert_eq!(MidiOutMessage::Clock.bytes(), vec![0xF8]);
+        assert_eq!(ClockSync::External.next(), ClockSync::Internal);
+    }
+}
@@ -0,0 +1,105 @@
+const VOLUME_MAX: u8 = 100;
+const VOLUME_STEP: u8 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrackMix {
+    pub volume: u8,
+    pub muted: bool,
+    pub midi_channel: Option<u8>, // MIDI output channel 0-15, None keeps the track internal
+}
+
+impl TrackMix {
+    pub fn new() -> Self {
+        Self {
+            volume: 80,
+            muted: false,
+            midi_channel: None,
+        }
+    }
+
+    pub fn gain(&self) -> f64 {
+        self.volume as f64 / VOLUME_MAX as f64
+    }
+}
+
+impl Default for TrackMix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    pub tracks: Vec<TrackMix>,
+    pub selected_track: usize,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        // The score is a single instrument for now, so there is one channel strip.
+        Self {
+            tracks: vec![TrackMix::new()],
+            selected_track: 0,
+        }
+    }
+
+    pub fn track(&self, track: usize) -> TrackMix {
+        self.tracks.get(track).copied().unwrap_or_default()
+    }
+
+    pub fn volume_up(&self) -> Self {
+        let mut new_mixer = self.clone();
+        let track = &mut new_mixer.tracks[self.selected_track];
+        track.volume = (track.volume + VOLUME_STEP).min(VOLUME_MAX);
+        new_mixer
+    }
+
+    pub fn volume_down(&self) -> Self {
+        let mut new_mixer = self.clone();
+        let track = &mut new_mixer.tracks[self.selected_track];
+        track.volume = track.volume.saturating_sub(VOLUME_STEP);
+        new_mixer
+    }
+
+    pub fn toggle_mute(&self) -> Self {
+        let mut new_mixer = self.clone();
+        let track = &mut new_mixer.tracks[self.selected_track];
+        track.muted = !track.muted;
+        new_mixer
+    }
+
+    // Cycles the MIDI output channel: off, 1 to 16, then off again.
+    pub fn next_midi_channel(&self) -> Self {
+        let mut new_mixer = self.clone();
+        let track = &mut new_mixer.tracks[self.selected_track];
+        track.midi_channel = match track.midi_channel {
+            None => Some(0),
+            Some(channel) if channel < 15 => Some(channel + 1),
+            Some(_) => None,
+        };
+        new_mixer
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_channel_cycle() {
+        // Channels run off, 1 to 16, then off again
+        let mut mixer = Mixer::new();
+        assert_eq!(mixer.track(0).midi_channel, None);
+        for _ in 0..16 {
+            mixer = mixer.next_midi_channel();
+        }
+        assert_eq!(mixer.track(0).midi_channel, Some(15));
+        assert_eq!(mixer.next_midi_channel().track(0).midi_channel, None);
+    }
+}
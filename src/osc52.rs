@@ -0,0 +1,62 @@
+// Copying to the system clipboard through the terminal's OSC 52 escape sequence.
+// Works over SSH and in tmux (with set-clipboard on), as the terminal does the copy.
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn copy_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoded: &str) -> Vec<u8> {
+        let sextets: Vec<u32> = encoded
+            .bytes()
+            .take_while(|&byte| byte != b'=')
+            .map(|byte| BASE64_ALPHABET.iter().position(|&c| c == byte).unwrap() as u32)
+            .collect();
+        let mut bytes = Vec::new();
+        for chunk in sextets.chunks(4) {
+            let n = chunk.iter().enumerate().fold(0, |n, (i, sextet)| n | sextet << (18 - 6 * i));
+            bytes.extend(n.to_be_bytes()[1..chunk.len()].iter());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(copy_sequence("hi"), "\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..8 {
+            assert_eq!(decode(&base64(&bytes[..len])), &bytes[..len]);
+        }
+        assert_eq!(decode(&base64(&bytes)), bytes);
+    }
+}
@@ -0,0 +1,44 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanelView {
+    Mixer,
+    NoteInspector,
+    Overview,
+    KeyCheck,
+    EventLog,
+    Arrangement,
+}
+
+impl PanelView {
+    pub fn all() -> [PanelView; 6] {
+        [
+            PanelView::Mixer,
+            PanelView::NoteInspector,
+            PanelView::Overview,
+            PanelView::KeyCheck,
+            PanelView::EventLog,
+            PanelView::Arrangement,
+        ]
+    }
+
+    pub fn next(&self) -> PanelView {
+        match self {
+            PanelView::Mixer => PanelView::NoteInspector,
+            PanelView::NoteInspector => PanelView::Overview,
+            PanelView::Overview => PanelView::KeyCheck,
+            PanelView::KeyCheck => PanelView::EventLog,
+            PanelView::EventLog => PanelView::Arrangement,
+            PanelView::Arrangement => PanelView::Mixer,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            PanelView::Mixer => "Mixer",
+            PanelView::NoteInspector => "Inspector",
+            PanelView::Overview => "Overview",
+            PanelView::KeyCheck => "Key",
+            PanelView::EventLog => "Log",
+            PanelView::Arrangement => "Arrange",
+        }
+    }
+}
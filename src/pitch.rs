@@ -129,6 +129,35 @@ impl Pitch {
         ))
     }
 
+    // Semitones above C0.
+    pub fn index(&self) -> u16 {
+        self.octave * 12 + self.tone.index()
+    }
+
+    pub fn from_index(index: u16) -> Option<Pitch> {
+        if index / 12 > OCTAVE_MAX {
+            return None;
+        }
+        Some(Pitch::new(Tone::from_index(index % 12), index / 12))
+    }
+
+    // MIDI note 60 is C4. Notes below C0 or above the top octave have no pitch.
+    pub fn from_midi(note: u8) -> Option<Pitch> {
+        Pitch::from_index((note as u16).checked_sub(12)?)
+    }
+
+    pub fn midi_note(&self) -> u8 {
+        (self.index() + 12) as u8
+    }
+
+    pub fn transpose(&self, semitones: i32) -> Option<Pitch> {
+        let index = self.index() as i32 + semitones;
+        if index < 0 {
+            return None;
+        }
+        Pitch::from_index(index as u16)
+    }
+
     pub fn frequency(&self, octave: u16) -> f64 {
         // Calculate the number of half steps from A4 (440 Hz)
         let half_steps_from_a4 = (octave as i32 - 4) * 12 + self.tone.index() as i32 - 9;
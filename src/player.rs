@@ -1,12 +1,33 @@
-use crate::score::{ActiveNote, Note, Score};
+use crate::score::{ActiveNote, Note, Score, BAR_B32, DEFAULT_VELOCITY};
 use std::collections::HashMap;
 use std::f64::consts::PI;
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex};
 use crate::loop_state::LoopState;
+use crate::metronome::Metronome;
+use crate::midi_output::{ClockSync, MidiOutMessage, CLOCKS_PER_B32};
+use crate::mixer::Mixer;
 use std::time::Instant;
 use crate::pitch::Pitch;
 
-#[derive(PartialEq, Clone, Copy)]
+// A note placed on the sample clock, so groove delays can start it between b32 steps.
+struct ScheduledNote {
+    note: Note,
+    start_tick: u64,
+    end_tick: u64,
+    midi_sounding: bool, // Note on sent to the MIDI output
+}
+
+// Length of a metronome click, and its pitches for the downbeat and the other beats.
+const CLICK_SECONDS: f64 = 0.04;
+const CLICK_ACCENT_HZ: f64 = 1760.0;
+const CLICK_HZ: f64 = 1320.0;
+
+struct Click {
+    tick: u64, // Samples since the click started
+    accent: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum PlayState {
     Stopped,
     Playing,
@@ -20,10 +41,28 @@ pub struct Player {
     state: PlayState,
     tick: u64,
     time_b32: u64,
-    active_notes: Vec<Note>,
+    active_notes: Vec<ScheduledNote>,
     ticks_per_b32: u64,
     loop_state: LoopState,
     preview_start: Option<Instant>,
+    mixer: Mixer,
+    // Notes held on a MIDI keyboard, with their own clock so they sound while stopped.
+    live_notes: Vec<Note>,
+    live_tick: u64,
+    // Recording keeps playback running past the last note.
+    recording: bool,
+    midi_out: Option<mpsc::Sender<MidiOutMessage>>,
+    clock_sync: ClockSync,
+    clock_tick: u64, // Samples since playback started, for sending clock
+    external_clocks: u64,
+    last_step_tick: Option<u64>,
+    metronome: Metronome,
+    click: Option<Click>,
+    count_in_tick: Option<u64>, // Set while counting in, playback starts when it runs out
+    start_time_b32: u64, // Where playback last started
+    end_time_b32: Option<u64>, // Playback stops here, as at the end of a played selection
+    return_on_stop: bool,
+    ringing_out: bool, // Past the song end, letting the last notes finish
 }
 
 impl Player {
@@ -31,7 +70,7 @@ impl Player {
         // Calculate ticks per b32 based on sample rate
         // For 120 BPM: 44100 samples/sec * 60 sec/min / 120 beats/min / 32 subdivisions = 689.0625 samples/b32
         // Rounding to 689 samples per b32 unit
-        let ticks_per_b32 = (sample_rate * 60 / score.lock().unwrap().bpm as u64) / 32;
+        let ticks_per_b32 = internal_ticks_per_b32(sample_rate, score.lock().unwrap().bpm);
 
         Player {
             score,
@@ -43,29 +82,96 @@ impl Player {
             ticks_per_b32,
             loop_state: LoopState::new(),
             preview_start: None,
+            mixer: Mixer::new(),
+            live_notes: Vec::new(),
+            live_tick: 0,
+            recording: false,
+            midi_out: None,
+            clock_sync: ClockSync::Internal,
+            clock_tick: 0,
+            external_clocks: 0,
+            last_step_tick: None,
+            metronome: Metronome::new(),
+            click: None,
+            count_in_tick: None,
+            start_time_b32: 0,
+            end_time_b32: None,
+            return_on_stop: false,
+            ringing_out: false,
         }
     }
 
     pub fn play(&mut self) {
+        if self.state != PlayState::Playing {
+            self.start_time_b32 = self.time_b32;
+            self.end_time_b32 = None;
+            self.ringing_out = false;
+            // An external clock decides when playback starts, so it gets no count-in
+            if self.metronome.counts_in(self.recording) && self.clock_sync != ClockSync::External {
+                self.count_in_tick = Some(0);
+            } else {
+                self.start_transport();
+            }
+        }
         self.state = PlayState::Playing;
     }
 
+    fn start_transport(&mut self) {
+        if self.time_b32 == 0 && self.tick == 0 {
+            self.clock_tick = 0;
+            self.send_transport(MidiOutMessage::Start);
+        } else {
+            self.send_transport(MidiOutMessage::Continue);
+        }
+    }
+
     pub fn pause(&mut self) {
+        // Nothing was started while counting in
+        if self.state == PlayState::Playing && self.count_in_tick.take().is_none() {
+            self.send_transport(MidiOutMessage::Stop);
+        }
+        // Notes still sounding start again on resume
+        self.silence_midi_notes();
         self.state = PlayState::Paused;
     }
 
     pub fn stop(&mut self) {
+        if self.state == PlayState::Playing && self.count_in_tick.take().is_none() {
+            self.send_transport(MidiOutMessage::Stop);
+        }
         self.state = PlayState::Stopped;
         self.time_b32 = 0;
         self.tick = 0;
-        self.active_notes.clear();
+        self.ringing_out = false;
+        self.clear_active_notes();
     }
 
     pub fn toggle_playback(&mut self) {
-        self.state = match self.state {
-            PlayState::Playing => PlayState::Paused,
-            PlayState::Paused | PlayState::Stopped => PlayState::Playing,
-            PlayState::Preview => PlayState::Paused,
+        match self.state {
+            PlayState::Playing => {
+                self.pause();
+                self.return_to_start();
+            }
+            PlayState::Preview => self.pause(),
+            PlayState::Paused | PlayState::Stopped => self.play(),
+        }
+    }
+
+    // Plays from `start_b32`, stopping at `end_b32` if given.
+    pub fn play_from(&mut self, start_b32: u64, end_b32: Option<u64>) {
+        self.set_time_b32(start_b32);
+        self.play();
+        self.end_time_b32 = end_b32;
+    }
+
+    pub fn set_return_on_stop(&mut self, return_on_stop: bool) {
+        self.return_on_stop = return_on_stop;
+    }
+
+    // Puts the playhead back where playback started, when that option is on.
+    fn return_to_start(&mut self) {
+        if self.return_on_stop {
+            self.set_time_b32(self.start_time_b32);
         }
     }
 
@@ -81,34 +187,206 @@ impl Player {
         self.pause();
         self.time_b32 = time_b32;
         self.tick = 0;
-        self.active_notes.clear();
-        self.update_active_notes();
+        self.clear_active_notes();
     }
 
     pub fn set_loop_state(&mut self, loop_state: LoopState) {
         self.loop_state = loop_state;
     }
 
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        // Sounding notes start again on the new channel, or stay off if muted
+        self.silence_midi_notes();
+        self.mixer = mixer;
+    }
+
+    pub fn set_metronome(&mut self, metronome: Metronome) {
+        self.metronome = metronome;
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn set_midi_out(&mut self, midi_out: mpsc::Sender<MidiOutMessage>) {
+        self.midi_out = Some(midi_out);
+    }
+
+    pub fn set_clock_sync(&mut self, clock_sync: ClockSync) {
+        self.clock_sync = clock_sync;
+        // Drop any tempo taken from an external clock
+        self.ticks_per_b32 = internal_ticks_per_b32(self.sample_rate, self.score.lock().unwrap().bpm);
+        self.last_step_tick = None;
+    }
+
+    // Incoming MIDI clock drives the playhead instead of the sample count, one b32 step
+    // every three clocks. The time between steps sets the tempo for note lengths.
+    pub fn external_clock(&mut self) {
+        if self.clock_sync != ClockSync::External || self.state != PlayState::Playing || self.ringing_out {
+            return;
+        }
+        if self.external_clocks.is_multiple_of(CLOCKS_PER_B32) {
+            if let Some(last_step_tick) = self.last_step_tick {
+                if self.tick > last_step_tick {
+                    self.ticks_per_b32 = self.tick - last_step_tick;
+                }
+            }
+            self.step(self.external_clocks != 0);
+            self.last_step_tick = Some(self.tick);
+        }
+        self.external_clocks += 1;
+    }
+
+    pub fn external_start(&mut self) {
+        if self.clock_sync != ClockSync::External {
+            return;
+        }
+        self.stop();
+        self.external_clocks = 0;
+        self.last_step_tick = None;
+        self.state = PlayState::Playing;
+    }
+
+    fn send_midi(&self, message: MidiOutMessage) {
+        if let Some(midi_out) = &self.midi_out {
+            let _ = midi_out.send(message);
+        }
+    }
+
+    fn send_transport(&self, message: MidiOutMessage) {
+        if self.clock_sync == ClockSync::Send {
+            self.send_midi(message);
+        }
+    }
+
+    // Sends note on and off as scheduled notes start and end on the sample clock.
+    fn send_midi_notes(&mut self) {
+        let track_mix = self.mixer.track(0);
+        let (Some(midi_out), Some(channel)) = (&self.midi_out, track_mix.midi_channel) else {
+            return;
+        };
+        for scheduled in &mut self.active_notes {
+            let pitch = scheduled.note.pitch;
+            if !scheduled.midi_sounding
+                && !track_mix.muted
+                && self.tick >= scheduled.start_tick
+                && self.tick < scheduled.end_tick
+            {
+                let _ = midi_out.send(MidiOutMessage::NoteOn(channel, pitch, scheduled.note.velocity));
+                scheduled.midi_sounding = true;
+            } else if scheduled.midi_sounding && self.tick >= scheduled.end_tick {
+                let _ = midi_out.send(MidiOutMessage::NoteOff(channel, pitch));
+                scheduled.midi_sounding = false;
+            }
+        }
+    }
+
+    // Ends the notes still sounding on the MIDI output.
+    fn silence_midi_notes(&mut self) {
+        let channel = self.mixer.track(0).midi_channel;
+        let (Some(midi_out), Some(channel)) = (&self.midi_out, channel) else {
+            return;
+        };
+        for scheduled in self.active_notes.iter_mut().filter(|scheduled| scheduled.midi_sounding) {
+            let _ = midi_out.send(MidiOutMessage::NoteOff(channel, scheduled.note.pitch));
+            scheduled.midi_sounding = false;
+        }
+    }
+
+    fn clear_active_notes(&mut self) {
+        self.silence_midi_notes();
+        self.active_notes.clear();
+    }
+
+    // Moves to the next b32 step and schedules its notes. Past the end of the range being
+    // played it stops, and past the song end it rings out.
+    fn step(&mut self, advance: bool) {
+        self.handle_time_update(advance);
+        if self.end_time_b32.is_some_and(|end_time_b32| self.time_b32 >= end_time_b32) {
+            self.pause();
+            self.return_to_start();
+        } else if self.recording || self.score.lock().unwrap().time_within_song(self.time_b32) {
+            self.update_active_notes();
+            if self.metronome.is_clicking(self.recording) {
+                self.start_click(self.time_b32);
+            }
+        } else {
+            self.ringing_out = true;
+        }
+    }
+
+    // Notes that run past the song end finish sounding before playback stops.
+    fn ring_out(&mut self) {
+        self.send_midi_notes();
+        if self.active_notes.iter().all(|scheduled| self.tick >= scheduled.end_tick) {
+            self.stop();
+            self.return_to_start();
+        } else {
+            self.tick += 1;
+        }
+    }
+
+    // Clicks every beat of the count-in, then starts playback from where it was.
+    fn count_in(&mut self) {
+        let count_in_tick = self.count_in_tick.unwrap_or(0);
+        if count_in_tick.is_multiple_of(self.ticks_per_b32) {
+            self.start_click(count_in_tick / self.ticks_per_b32);
+        }
+        if count_in_tick + 1 >= self.metronome.count_in_bars * BAR_B32 * self.ticks_per_b32 {
+            self.count_in_tick = None;
+            self.start_transport();
+        } else {
+            self.count_in_tick = Some(count_in_tick + 1);
+        }
+    }
+
+    fn start_click(&mut self, time_b32: u64) {
+        if let Some(accent) = Metronome::click_at(time_b32) {
+            self.click = Some(Click { tick: 0, accent });
+        }
+    }
+
+    // The click decays quickly so successive beats stay distinct.
+    fn click_amplitude(&mut self) -> f64 {
+        let Some(click) = &mut self.click else {
+            return 0.0;
+        };
+        let seconds = click.tick as f64 / self.sample_rate as f64;
+        let frequency = if click.accent { CLICK_ACCENT_HZ } else { CLICK_HZ };
+        let amplitude = (2.0 * PI * frequency * seconds).sin() * (-seconds / (CLICK_SECONDS / 4.0)).exp();
+        click.tick += 1;
+        if seconds >= CLICK_SECONDS {
+            self.click = None;
+        }
+        amplitude * self.metronome.volume as f64 / 100.0
+    }
+
     fn update_active_notes(&mut self) {
         // Get notes starting at current time
-        let new_notes = self
-            .score
-            .lock()
-            .unwrap()
-            .notes_starting_at_time(self.time_b32);
+        let score = self.score.lock().unwrap();
+        let new_notes = score.sounding_notes_starting_at_time(self.time_b32);
 
-        // Remove finished notes and add new ones
-        self.active_notes
-            .retain(|note| note.onset_b32 + note.duration_b32 > self.time_b32);
-        self.active_notes.extend(new_notes);
+        // Remove finished notes and schedule new ones after their groove delay
+        let tick = self.tick;
+        self.active_notes.retain(|scheduled| scheduled.end_tick > tick);
+        for note in new_notes {
+            let delay_ticks = score.groove.delay_b32_hundredths(note.onset_b32, 0) * self.ticks_per_b32 / 100;
+            let start_tick = tick + delay_ticks;
+            self.active_notes.push(ScheduledNote {
+                note,
+                start_tick,
+                end_tick: start_tick + note.duration_b32 * self.ticks_per_b32,
+                midi_sounding: false,
+            });
+        }
     }
 
     pub fn state(&self) -> PlayState {
         return self.state;
     }
 
-    fn handle_time_update(&mut self) {
-        if self.tick != 0 {
+    fn handle_time_update(&mut self, advance: bool) {
+        if advance {
             self.time_b32 += 1;
         }
 
@@ -117,7 +395,7 @@ impl Player {
                 if self.time_b32 >= end || self.time_b32 < start {
                     self.time_b32 = start;
                     self.tick = 0;
-                    self.active_notes.clear();
+                    self.clear_active_notes();
                 }
             }
         }
@@ -126,21 +404,63 @@ impl Player {
     pub fn preview_note(&mut self, pitch: Pitch) {
         self.state = PlayState::Preview;
         self.active_notes.clear();
-        self.active_notes.push(Note {
+        self.active_notes.push(ScheduledNote {
+            note: Note {
+                pitch,
+                onset_b32: 0,
+                duration_b32: 16,
+                velocity: DEFAULT_VELOCITY,
+            },
+            start_tick: self.tick,
+            end_tick: u64::MAX, // Ended by clear_preview
+            midi_sounding: false,
+        });
+        self.preview_start = Some(Instant::now());
+    }
+
+    // Moves the playhead to `time_b32` and briefly sounds the notes under it, as when
+    // dragging it by hand. Repeated calls keep the sound going.
+    pub fn scrub_to(&mut self, time_b32: u64) {
+        self.set_time_b32(time_b32);
+        let active_notes = self.score.lock().unwrap().notes_active_at_time(time_b32);
+        self.state = PlayState::Preview;
+        for active_note in active_notes {
+            self.active_notes.push(ScheduledNote {
+                note: active_note.note,
+                start_tick: self.tick,
+                end_tick: u64::MAX, // Ended by clear_preview
+                midi_sounding: false,
+            });
+        }
+        self.preview_start = Some(Instant::now());
+    }
+
+    pub fn live_note_on(&mut self, pitch: Pitch, velocity: u8) {
+        self.live_note_off(pitch);
+        self.live_notes.push(Note {
             pitch,
             onset_b32: 0,
-            duration_b32: 16,
+            duration_b32: 0,
+            velocity,
         });
-        self.preview_start = Some(Instant::now());
+    }
+
+    pub fn live_note_off(&mut self, pitch: Pitch) {
+        self.live_notes.retain(|note| note.pitch != pitch);
     }
 
     pub fn clear_preview(&mut self) {
         if self.state == PlayState::Preview {
             self.state = PlayState::Stopped;
-            self.active_notes.clear();
+            self.clear_active_notes();
             self.preview_start = None;
         }
     }
+
+    fn amplitude(&self, note: Note, tick: u64) -> f64 {
+        let frequency = note.pitch.frequency(note.pitch.octave);
+        (2.0 * PI * frequency * (tick as f64) / self.sample_rate as f64).sin() * note.velocity as f64 / 127.0
+    }
 }
 
 impl Iterator for Player {
@@ -156,36 +476,140 @@ impl Iterator for Player {
         }
 
         match self.state {
+            PlayState::Playing if self.count_in_tick.is_some() => self.count_in(),
+            PlayState::Playing if self.ringing_out => self.ring_out(),
             PlayState::Playing => {
-                if self.tick % self.ticks_per_b32 == 0 {
-                    if self.score.lock().unwrap().time_within_song(self.time_b32) {
-                        self.update_active_notes();
-                        self.handle_time_update();
-                    } else {
-                        self.active_notes.clear();
-                        self.stop();
+                // With an external clock the steps come from external_clock
+                if self.clock_sync != ClockSync::External && self.tick.is_multiple_of(self.ticks_per_b32) {
+                    // Advance first, so each step only schedules the notes starting on it
+                    self.step(self.tick != 0);
+                }
+                if self.clock_sync == ClockSync::Send {
+                    // Each sample moves the clock phase on by three, so this is true once per clock
+                    if (self.clock_tick * CLOCKS_PER_B32) % self.ticks_per_b32 < CLOCKS_PER_B32 {
+                        self.send_midi(MidiOutMessage::Clock);
                     }
+                    self.clock_tick += 1;
                 }
+                self.send_midi_notes();
                 self.tick += 1;
             }
             PlayState::Preview => {
                 // Just continue playing the preview note
                 self.tick += 1;
             }
-            _ => return Some(0.0),
+            _ if self.live_notes.is_empty() && self.click.is_none() => return Some(0.0),
+            _ => (),
         }
 
-        if self.active_notes.is_empty() {
-            return Some(0.0);
+        let mut total_amplitudes: f64 = 0.0;
+        let mut sounding = 0;
+
+        // Previews and live notes stay audible on a muted track so auditioning still works.
+        let track_mix = self.mixer.track(0);
+        if !(track_mix.muted && self.state == PlayState::Playing) {
+            for scheduled in &self.active_notes {
+                // Notes delayed by the groove wait for their start tick
+                if scheduled.start_tick > self.tick || self.tick >= scheduled.end_tick {
+                    continue;
+                }
+                total_amplitudes += self.amplitude(scheduled.note, self.tick);
+                sounding += 1;
+            }
         }
 
-        let mut total_amplitudes: f64 = 0.0;
-        for note in &self.active_notes {
-            let frequency = note.pitch.frequency(note.pitch.octave);
-            total_amplitudes +=
-                (2.0 * PI * frequency * (self.tick as f64) / self.sample_rate as f64).sin();
+        self.live_tick += 1;
+        for note in &self.live_notes {
+            total_amplitudes += self.amplitude(*note, self.live_tick);
+            sounding += 1;
         }
 
-        Some(total_amplitudes / self.active_notes.len() as f64)
+        let click = self.click_amplitude();
+        if sounding == 0 {
+            return Some(click);
+        }
+        Some(total_amplitudes / sounding as f64 * track_mix.gain() + click)
+    }
+}
+
+fn internal_ticks_per_b32(sample_rate: u64, bpm: u16) -> u64 {
+    (sample_rate * 60 / bpm as u64) / 32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::Tone;
+    use crate::score::note;
+
+    // 50 samples per b32 step at 120 BPM
+    const TEST_SAMPLE_RATE: u64 = 3200;
+    const TICKS_PER_B32: usize = 50;
+
+    fn create_test_player(duration_b32: u64, length_b32: Option<u64>) -> Player {
+        let mut score = Score::new(120);
+        if duration_b32 > 0 {
+            score.insert_note(note(Pitch::new(Tone::C, 4), 0, duration_b32));
+        }
+        score.length_b32 = length_b32;
+        Player::create(Arc::new(Mutex::new(score)), TEST_SAMPLE_RATE)
+    }
+
+    fn run_steps(player: &mut Player, steps: usize) {
+        player.by_ref().take(TICKS_PER_B32 * steps).for_each(drop);
+    }
+
+    #[test]
+    fn test_play_from_and_return_on_stop() {
+        let mut player = create_test_player(64, None);
+
+        // A played selection stops at its end
+        player.play_from(8, Some(16));
+        run_steps(&mut player, 10);
+        assert_eq!(player.state(), PlayState::Paused);
+        assert_eq!(player.current_time_b32(), 16);
+
+        // With return on stop the playhead goes back to where playback started
+        player.set_return_on_stop(true);
+        player.play_from(8, Some(16));
+        run_steps(&mut player, 10);
+        assert_eq!(player.current_time_b32(), 8);
+
+        player.play_from(24, None);
+        run_steps(&mut player, 4);
+        player.toggle_playback();
+        assert_eq!(player.current_time_b32(), 24);
+    }
+
+    #[test]
+    fn test_scrub() {
+        let mut player = create_test_player(16, None);
+        player.scrub_to(8);
+        assert_eq!(player.state(), PlayState::Preview);
+        assert_eq!(player.current_time_b32(), 8);
+        // The note under the playhead sounds, from its second sample on
+        player.next();
+        assert!(player.next().unwrap() != 0.0);
+    }
+
+    #[test]
+    fn test_song_end() {
+        // An empty song plays through its bar of silence
+        let mut player = create_test_player(0, None);
+        player.play();
+        run_steps(&mut player, 16);
+        player.next();
+        assert_eq!(player.current_time_b32(), 16);
+        run_steps(&mut player, 20);
+        assert_eq!(player.state(), PlayState::Stopped);
+
+        // A note running past a set length still sounds to its end
+        let mut player = create_test_player(64, Some(32));
+        player.play();
+        run_steps(&mut player, 48);
+        assert_eq!(player.state(), PlayState::Playing);
+        assert_eq!(player.current_time_b32(), 32);
+        run_steps(&mut player, 20);
+        assert_eq!(player.state(), PlayState::Stopped);
     }
 }
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::resolution::Resolution;
+use crate::score::Note;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuantizeTarget {
+    Onsets,
+    Lengths,
+    Both,
+}
+
+impl QuantizeTarget {
+    pub fn next(&self) -> QuantizeTarget {
+        match self {
+            QuantizeTarget::Onsets => QuantizeTarget::Lengths,
+            QuantizeTarget::Lengths => QuantizeTarget::Both,
+            QuantizeTarget::Both => QuantizeTarget::Onsets,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            QuantizeTarget::Onsets => "onsets",
+            QuantizeTarget::Lengths => "lengths",
+            QuantizeTarget::Both => "onsets+lengths",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantize {
+    pub resolution: Resolution,
+    pub target: QuantizeTarget,
+    pub strength_percent: u64, // How far notes move towards the grid
+    pub swing_percent: u64,    // Delay of every second grid point, as part of a grid step
+}
+
+impl Quantize {
+    pub fn new(resolution: Resolution) -> Quantize {
+        Quantize {
+            resolution,
+            target: QuantizeTarget::Onsets,
+            strength_percent: 100,
+            swing_percent: 0,
+        }
+    }
+
+    pub fn with_resolution(&self, resolution: Resolution) -> Quantize {
+        let mut quantize = *self;
+        quantize.resolution = resolution;
+        quantize
+    }
+
+    pub fn next_target(&self) -> Quantize {
+        let mut quantize = *self;
+        quantize.target = self.target.next();
+        quantize
+    }
+
+    pub fn next_strength(&self) -> Quantize {
+        let mut quantize = *self;
+        quantize.strength_percent = match self.strength_percent {
+            100 => 75,
+            75 => 50,
+            50 => 25,
+            _ => 100,
+        };
+        quantize
+    }
+
+    pub fn next_swing(&self) -> Quantize {
+        let mut quantize = *self;
+        quantize.swing_percent = match self.swing_percent {
+            0 => 25,
+            25 => 50,
+            _ => 0,
+        };
+        quantize
+    }
+
+    pub fn apply(&self, note: Note) -> Note {
+        let grid = self.resolution.duration_b32();
+        let mut new_note = note;
+        if self.target != QuantizeTarget::Lengths {
+            let below = note.onset_b32 / grid;
+            let target = [self.grid_point(below), self.grid_point(below + 1)]
+                .into_iter()
+                .min_by_key(|point| point.abs_diff(note.onset_b32))
+                .unwrap();
+            new_note.onset_b32 = self.approach(note.onset_b32, target);
+        }
+        if self.target != QuantizeTarget::Onsets {
+            let target = ((note.duration_b32 + grid / 2) / grid * grid).max(grid);
+            new_note.duration_b32 = self.approach(note.duration_b32, target).max(1);
+        }
+        new_note
+    }
+
+    fn grid_point(&self, index: u64) -> u64 {
+        let grid = self.resolution.duration_b32();
+        if index % 2 == 1 {
+            index * grid + grid * self.swing_percent / 100
+        } else {
+            index * grid
+        }
+    }
+
+    fn approach(&self, from: u64, to: u64) -> u64 {
+        let moved = (to as i64 - from as i64) * self.strength_percent as i64 / 100;
+        (from as i64 + moved) as u64
+    }
+}
+
+impl fmt::Display for Quantize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} strength {}% swing {}%",
+            self.resolution.as_str(),
+            self.target.as_str(),
+            self.strength_percent,
+            self.swing_percent
+        )
+    }
+}
+
+// Random onset and velocity offsets. The offsets are derived from the seed and the
+// note itself, so the same seed always gives the same result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Humanize {
+    pub onset_range_b32: u64,
+    pub velocity_range: u8,
+    pub seed: u64,
+}
+
+impl Humanize {
+    pub fn new() -> Humanize {
+        Humanize {
+            onset_range_b32: 1,
+            velocity_range: 12,
+            seed: 1,
+        }
+    }
+
+    pub fn next_seed(&self) -> Humanize {
+        let mut humanize = *self;
+        humanize.seed = self.seed.wrapping_add(1);
+        humanize
+    }
+
+    pub fn apply(&self, note: Note) -> Note {
+        let hash = splitmix64(self.seed ^ (note.onset_b32 << 16) ^ note.pitch.index() as u64);
+        let onset_offset = jitter(hash, self.onset_range_b32);
+        let velocity_offset = jitter(hash >> 32, self.velocity_range as u64);
+
+        let mut new_note = note;
+        new_note.onset_b32 = (note.onset_b32 as i64 + onset_offset).max(0) as u64;
+        new_note.velocity = (note.velocity as i64 + velocity_offset).clamp(1, 127) as u8;
+        new_note
+    }
+}
+
+impl Default for Humanize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Humanize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "onset ±{} velocity ±{} seed {}",
+            self.onset_range_b32, self.velocity_range, self.seed
+        )
+    }
+}
+
+// Offset in -range..=range.
+fn jitter(hash: u64, range: u64) -> i64 {
+    (hash % (2 * range + 1)) as i64 - range as i64
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,107 @@
+use crate::pitch::Pitch;
+use crate::score::Note;
+
+struct HeldNote {
+    pitch: Pitch,
+    velocity: u8,
+    onset_b32: u64,
+}
+
+impl HeldNote {
+    fn until(&self, end_b32: u64) -> Note {
+        Note {
+            pitch: self.pitch,
+            onset_b32: self.onset_b32,
+            duration_b32: end_b32.saturating_sub(self.onset_b32).max(1),
+            velocity: self.velocity,
+        }
+    }
+}
+
+// Turns notes played during playback into score notes, timed by the playback position.
+// Each pass through a loop is a new layer.
+pub struct Recorder {
+    held: Vec<HeldNote>,
+    last_time_b32: u64,
+    layer: u32,
+}
+
+impl Recorder {
+    pub fn new(time_b32: u64) -> Recorder {
+        Recorder {
+            held: Vec::new(),
+            last_time_b32: time_b32,
+            layer: 1,
+        }
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    pub fn note_on(&mut self, pitch: Pitch, velocity: u8, time_b32: u64) {
+        self.held.retain(|held| held.pitch != pitch);
+        self.held.push(HeldNote {
+            pitch,
+            velocity,
+            onset_b32: time_b32,
+        });
+    }
+
+    // The finished note, if the pitch was being held.
+    pub fn note_off(&mut self, pitch: Pitch, time_b32: u64) -> Option<Note> {
+        let index = self.held.iter().position(|held| held.pitch == pitch)?;
+        Some(self.held.remove(index).until(time_b32))
+    }
+
+    // Follows the playback position. When it jumps back, as at a loop restart, a new
+    // layer starts: held notes are cut at the end of the pass and returned, and carry
+    // on from the new position.
+    pub fn advance(&mut self, time_b32: u64) -> Option<Vec<Note>> {
+        let pass_end_b32 = self.last_time_b32 + 1;
+        let wrapped = time_b32 < self.last_time_b32;
+        self.last_time_b32 = time_b32;
+        if !wrapped {
+            return None;
+        }
+
+        self.layer += 1;
+        let cut_notes = self.held.iter().map(|held| held.until(pass_end_b32)).collect();
+        for held in &mut self.held {
+            held.onset_b32 = time_b32;
+        }
+        Some(cut_notes)
+    }
+
+    // Ends every held note at `time_b32`.
+    pub fn finish(&mut self, time_b32: u64) -> Vec<Note> {
+        self.held.drain(..).map(|held| held.until(time_b32)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::Tone;
+
+    #[test]
+    fn test_recorder_loop_layers() {
+        let c4 = Pitch::new(Tone::C, 4);
+        let e4 = Pitch::new(Tone::E, 4);
+        let mut recorder = Recorder::new(32);
+        recorder.note_on(c4, 90, 33);
+        recorder.note_on(e4, 80, 40);
+        assert!(recorder.advance(44).is_none());
+        let note = recorder.note_off(c4, 45).unwrap();
+        assert_eq!((note.onset_b32, note.duration_b32, note.velocity), (33, 12, 90));
+        assert!(recorder.note_off(c4, 46).is_none());
+
+        // Looping back from 63 to 32 cuts the held E4 at the loop end and starts layer 2
+        recorder.advance(63);
+        let cut_notes = recorder.advance(32).unwrap();
+        assert_eq!(recorder.layer(), 2);
+        assert_eq!((cut_notes[0].onset_b32, cut_notes[0].duration_b32), (40, 24));
+        let notes = recorder.finish(36);
+        assert_eq!((notes[0].pitch, notes[0].onset_b32, notes[0].duration_b32), (e4, 32, 4));
+    }
+}
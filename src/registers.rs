@@ -0,0 +1,111 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::score::Score;
+
+const HISTORY_LIMIT: usize = 10;
+
+// Named clipboards ("a to "z) plus a ring of the most recent yanks and cuts.
+#[derive(Debug, Clone)]
+pub struct Registers {
+    named: HashMap<char, Score>,
+    history: VecDeque<Score>,
+    history_index: usize,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            named: HashMap::new(),
+            history: VecDeque::new(),
+            history_index: 0,
+        }
+    }
+
+    pub fn is_register_name(name: char) -> bool {
+        name.is_ascii_lowercase()
+    }
+
+    // Every store goes into the history ring, and into `register` when one is given.
+    pub fn store(&mut self, register: Option<char>, score: Score) {
+        if let Some(register) = register {
+            self.named.insert(register, score.clone());
+        }
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_back();
+        }
+        self.history.push_front(score);
+        self.history_index = 0;
+    }
+
+    pub fn get(&self, register: char) -> Option<&Score> {
+        self.named.get(&register)
+    }
+
+    // Steps back through the history ring, wrapping to the newest entry.
+    pub fn cycle_history(&mut self) -> Option<&Score> {
+        if self.history.is_empty() {
+            return None;
+        }
+        self.history_index = (self.history_index + 1) % self.history.len();
+        self.history.get(self.history_index)
+    }
+
+    pub fn history_position(&self) -> (usize, usize) {
+        (self.history_index + 1, self.history.len())
+    }
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::{Pitch, Tone};
+    use crate::score::note;
+
+    fn score_with_note_at(onset_b32: u64) -> Score {
+        let mut score = Score::new(120);
+        score.insert_note(note(Pitch::new(Tone::C, 4), onset_b32, 8));
+        score
+    }
+
+    fn onset(score: Option<&Score>) -> Option<u64> {
+        score.and_then(|score| score.song_notes().first().map(|note| note.onset_b32))
+    }
+
+    #[test]
+    fn test_named_registers() {
+        let mut registers = Registers::new();
+        assert!(Registers::is_register_name('a'));
+        assert!(!Registers::is_register_name('A'));
+
+        registers.store(Some('a'), score_with_note_at(0));
+        registers.store(None, score_with_note_at(8));
+        assert_eq!(onset(registers.get('a')), Some(0));
+        assert!(registers.get('b').is_none());
+        // Storing to a register again replaces it
+        registers.store(Some('a'), score_with_note_at(16));
+        assert_eq!(onset(registers.get('a')), Some(16));
+    }
+
+    #[test]
+    fn test_history_ring() {
+        let mut registers = Registers::new();
+        assert!(registers.cycle_history().is_none());
+        for onset_b32 in 0..12 {
+            registers.store(None, score_with_note_at(onset_b32));
+        }
+        // Only the last ten are kept, newest first
+        assert_eq!(registers.history_position(), (1, 10));
+        assert_eq!(onset(registers.cycle_history()), Some(10));
+        for _ in 0..8 {
+            registers.cycle_history();
+        }
+        assert_eq!(registers.history_position(), (10, 10));
+        assert_eq!(onset(registers.cycle_history()), Some(11));
+    }
+}
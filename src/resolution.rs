@@ -1,4 +1,4 @@
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Resolution {
     Time1_4,
     Time1_8,
@@ -16,15 +16,6 @@ impl Resolution {
         }
     }
 
-    pub fn bar_length_in_beats(&self) -> usize {
-        match self {
-            Resolution::Time1_4 => 4,
-            Resolution::Time1_8 => 8,
-            Resolution::Time1_16 => 16,
-            Resolution::Time1_32 => 32,
-        }
-    }
-
     pub fn duration_b32(&self) -> u64 {
         match self {
             Resolution::Time1_4 => 8,
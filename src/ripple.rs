@@ -0,0 +1,18 @@
+// Where a time point ends up when time is inserted or deleted ahead of it.
+
+pub fn after_insert(time_point_b32: u64, at_b32: u64, length_b32: u64) -> u64 {
+    if time_point_b32 >= at_b32 {
+        time_point_b32 + length_b32
+    } else {
+        time_point_b32
+    }
+}
+
+// Points inside the deleted span collapse onto its start.
+pub fn after_delete(time_point_b32: u64, start_b32: u64, length_b32: u64) -> u64 {
+    if time_point_b32 >= start_b32 + length_b32 {
+        time_point_b32 - length_b32
+    } else {
+        time_point_b32.min(start_b32)
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+
+use crate::pitch::{Pitch, Tone};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Custom(u16), // Bit n set when the note n semitones above the tonic is in the scale
+}
+
+impl Scale {
+    pub fn mask(&self) -> u16 {
+        let intervals: &[u16] = match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Custom(mask) => return *mask,
+        };
+        intervals.iter().fold(0, |mask, interval| mask | 1 << interval)
+    }
+
+    pub fn next(&self) -> Scale {
+        match self {
+            Scale::Chromatic => Scale::Major,
+            Scale::Major => Scale::Minor,
+            Scale::Minor => Scale::Dorian,
+            Scale::Dorian => Scale::Phrygian,
+            Scale::Phrygian => Scale::Lydian,
+            Scale::Lydian => Scale::Mixolydian,
+            Scale::Mixolydian => Scale::Locrian,
+            Scale::Locrian => Scale::MajorPentatonic,
+            Scale::MajorPentatonic => Scale::MinorPentatonic,
+            Scale::MinorPentatonic => Scale::Chromatic,
+            Scale::Custom(_) => Scale::Chromatic,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Scale::Chromatic => "chromatic",
+            Scale::Major => "major",
+            Scale::Minor => "minor",
+            Scale::Dorian => "dorian",
+            Scale::Phrygian => "phrygian",
+            Scale::Lydian => "lydian",
+            Scale::Mixolydian => "mixolydian",
+            Scale::Locrian => "locrian",
+            Scale::MajorPentatonic => "major_pentatonic",
+            Scale::MinorPentatonic => "minor_pentatonic",
+            Scale::Custom(_) => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scale> {
+        let scale = match name {
+            "chromatic" => Scale::Chromatic,
+            "major" => Scale::Major,
+            "minor" => Scale::Minor,
+            "dorian" => Scale::Dorian,
+            "phrygian" => Scale::Phrygian,
+            "lydian" => Scale::Lydian,
+            "mixolydian" => Scale::Mixolydian,
+            "locrian" => Scale::Locrian,
+            "major_pentatonic" => Scale::MajorPentatonic,
+            "minor_pentatonic" => Scale::MinorPentatonic,
+            _ => return None,
+        };
+        Some(scale)
+    }
+
+    // Semitones above the tonic, lowest first.
+    pub fn intervals(&self) -> Vec<u16> {
+        (0..12).filter(|interval| self.mask() & 1 << interval != 0).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub tonic: Tone,
+    pub scale: Scale,
+}
+
+impl Key {
+    pub fn new(tonic: Tone, scale: Scale) -> Key {
+        Key { tonic, scale }
+    }
+
+    fn interval_above_tonic(&self, tone: Tone) -> u16 {
+        (tone.index() + 12 - self.tonic.index()) % 12
+    }
+
+    pub fn contains(&self, pitch: Pitch) -> bool {
+        self.scale.mask() & 1 << self.interval_above_tonic(pitch.tone) != 0
+    }
+
+    pub fn is_tonic(&self, pitch: Pitch) -> bool {
+        pitch.tone == self.tonic
+    }
+
+    // Closest pitch above `pitch` that is in the key.
+    pub fn next_in_key(&self, pitch: Pitch) -> Option<Pitch> {
+        let mut next_pitch = pitch.next();
+        while let Some(candidate) = next_pitch {
+            if self.contains(candidate) {
+                return Some(candidate);
+            }
+            next_pitch = candidate.next();
+        }
+        None
+    }
+
+    // Closest pitch below `pitch` that is in the key.
+    pub fn prev_in_key(&self, pitch: Pitch) -> Option<Pitch> {
+        let mut prev_pitch = pitch.prev();
+        while let Some(candidate) = prev_pitch {
+            if self.contains(candidate) {
+                return Some(candidate);
+            }
+            prev_pitch = candidate.prev();
+        }
+        None
+    }
+
+    // Moves by scale degrees. An out-of-key pitch counts the step to its neighbouring
+    // in-key pitch as the first degree.
+    pub fn transpose_diatonic(&self, pitch: Pitch, steps: i32) -> Option<Pitch> {
+        let mut new_pitch = pitch;
+        for _ in 0..steps.unsigned_abs() {
+            new_pitch = if steps > 0 {
+                self.next_in_key(new_pitch)?
+            } else {
+                self.prev_in_key(new_pitch)?
+            };
+        }
+        Some(new_pitch)
+    }
+
+    pub fn next_tonic(&self) -> Key {
+        let mut new_key = *self;
+        new_key.tonic = Tone::from_index((self.tonic.index() + 1) % 12);
+        new_key
+    }
+
+    pub fn next_scale(&self) -> Key {
+        let mut new_key = *self;
+        new_key.scale = self.scale.next();
+        new_key
+    }
+
+    // Adds or removes the tone from the key, turning it into a custom scale.
+    pub fn toggle_tone(&self, tone: Tone) -> Key {
+        let mut new_key = *self;
+        let interval = self.interval_above_tonic(tone);
+        // Keep the tonic so the scale always has a root.
+        if interval != 0 {
+            new_key.scale = Scale::Custom(self.scale.mask() ^ 1 << interval);
+        }
+        new_key
+    }
+}
+
+impl Default for Key {
+    fn default() -> Key {
+        Key::new(Tone::C, Scale::Chromatic)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.tonic.as_str(), self.scale.as_str())
+    }
+}
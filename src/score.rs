@@ -4,16 +4,42 @@ use std::{collections::HashMap, f32::MAX};
 use log::debug;
 
 use crate::{
-    pitch::{Pitch, Tone},
+    pitch::{Pitch, Tone, OCTAVE_MAX},
     selection_buffer,
 };
-use crate::selection_range::SelectionRange;
+use crate::arrangement::{Arrangement, Clip, Pattern};
+use crate::groove::Groove;
+use crate::marker::Marker;
+use crate::ripple;
+use crate::scale::Key;
+use crate::selection_buffer::PasteMode;
+use crate::selection_range::{SelectionMode, SelectionRange};
+use crate::transform::Transform;
+
+pub const DEFAULT_VELOCITY: u8 = 100;
+// The score is in 4/4 with 32 b32 units to the bar.
+pub const BAR_B32: u64 = 32;
+
+// Silence after the last bar with notes when the song length follows the content.
+const SONG_TAIL_B32: u64 = BAR_B32;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Note {
     pub pitch: Pitch,
     pub onset_b32: u64,
     pub duration_b32: u64,
+    pub velocity: u8, // 1-127, as in MIDI
+}
+
+// A note at the default velocity, for building test scores
+#[cfg(test)]
+pub fn note(pitch: Pitch, onset_b32: u64, duration_b32: u64) -> Note {
+    Note {
+        pitch,
+        onset_b32,
+        duration_b32,
+        velocity: DEFAULT_VELOCITY,
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -32,11 +58,42 @@ pub struct ActiveNote {
 #[derive(Debug, Clone)]
 pub struct Score {
     pub bpm: u16,
+    pub key: Key,
+    pub groove: Groove,
     pub notes: HashMap<u64, Vec<Note>>,
     pub active_notes: HashMap<u64, Vec<ActiveNote>>,
+    pub arrangement: Arrangement,
+    pub markers: Vec<Marker>, // In time order, at most one per time point
+    pub length_b32: Option<u64>, // Set song length, or None to follow the content
+    // Notes played by the arrangement's clips, kept apart from the editable notes.
+    clip_notes: HashMap<u64, Vec<Note>>,
+    clip_active_notes: HashMap<u64, Vec<ActiveNote>>,
 }
 
 impl Score {
+    pub fn new(bpm: u16) -> Score {
+        Score {
+            bpm,
+            key: Key::default(),
+            groove: Groove::new(),
+            notes: HashMap::new(),
+            active_notes: HashMap::new(),
+            arrangement: Arrangement::new(),
+            markers: Vec::new(),
+            length_b32: None,
+            clip_notes: HashMap::new(),
+            clip_active_notes: HashMap::new(),
+        }
+    }
+
+    // Empty score that keeps this score's settings.
+    fn empty_copy(&self) -> Score {
+        let mut score = Score::new(self.bpm);
+        score.key = self.key;
+        score.groove = self.groove.clone();
+        score
+    }
+
     pub fn notes_starting_at_time(&self, onset_b32: u64) -> Vec<Note> {
         self.notes
             .get(&onset_b32)
@@ -46,20 +103,89 @@ impl Score {
             .collect()
     }
 
+    // Editable notes and clip notes together, for reading the whole song.
+    fn all_notes(&self) -> impl Iterator<Item = &Note> {
+        self.notes.values().chain(self.clip_notes.values()).flatten()
+    }
+
+    // Every note that plays, including those played by clips, in no particular order.
+    pub fn song_notes(&self) -> Vec<Note> {
+        self.all_notes().copied().collect()
+    }
+
+    // Every note starting at the time point, including those played by clips.
+    pub fn sounding_notes_starting_at_time(&self, onset_b32: u64) -> Vec<Note> {
+        let mut notes = self.notes_starting_at_time(onset_b32);
+        notes.extend(self.clip_notes.get(&onset_b32).into_iter().flatten());
+        notes
+    }
+
     pub fn time_within_song(&self, time_point_b32: u64) -> bool {
+        self.song_length_b32() > time_point_b32
+    }
+
+    // Where playback ends. Without a set length that is the end of the last bar with
+    // notes plus a tail, so an empty song still plays a bar.
+    pub fn song_length_b32(&self) -> u64 {
+        self.length_b32
+            .unwrap_or_else(|| self.end_time_b32().div_ceil(BAR_B32) * BAR_B32 + SONG_TAIL_B32)
+    }
+
+    // Time point just past the end of the last note in the song.
+    pub fn end_time_b32(&self) -> u64 {
         let mut last_time_point_in_song = 0;
 
-        for (_, notes_at_onset) in &self.notes {
-            for note in notes_at_onset {
-                if note.onset_b32 + note.duration_b32 > last_time_point_in_song {
-                    last_time_point_in_song = note.onset_b32 + note.duration_b32
-                }
+        for note in self.all_notes() {
+            if note.onset_b32 + note.duration_b32 > last_time_point_in_song {
+                last_time_point_in_song = note.onset_b32 + note.duration_b32
+            }
+        }
+        last_time_point_in_song
+    }
+
+    // Number of note onsets in each bar, from the first bar to the last bar with a note.
+    pub fn onsets_per_bar(&self) -> Vec<usize> {
+        let bar_count = self.end_time_b32().div_ceil(BAR_B32) as usize;
+        let mut counts = vec![0; bar_count];
+        for note in self.all_notes() {
+            if let Some(count) = counts.get_mut((note.onset_b32 / BAR_B32) as usize) {
+                *count += 1;
             }
         }
-        last_time_point_in_song > time_point_b32
+        counts
+    }
+
+    // Distinct pitches with at least one note, lowest first.
+    pub fn used_pitches(&self) -> Vec<Pitch> {
+        let mut pitches: Vec<Pitch> = self.all_notes().map(|note| note.pitch).collect();
+        pitches.sort_by_key(|pitch| pitch.index());
+        pitches.dedup();
+        pitches
+    }
+
+    // Notes whose pitch is outside the song key, in time order.
+    pub fn notes_out_of_key(&self) -> Vec<Note> {
+        let mut notes: Vec<Note> = self
+            .all_notes()
+            .copied()
+            .filter(|note| !self.key.contains(note.pitch))
+            .collect();
+        notes.sort_by_key(|note| (note.onset_b32, note.pitch.index()));
+        notes
     }
 
     pub fn insert_or_remove(&mut self, pitch: Pitch, onset_b32: u64, duration_b32: u64) {
+        self.insert_or_remove_note(Note {
+            pitch,
+            onset_b32,
+            duration_b32,
+            velocity: DEFAULT_VELOCITY,
+        });
+    }
+
+    pub fn insert_or_remove_note(&mut self, note_to_insert: Note) {
+        let pitch = note_to_insert.pitch;
+        let onset_b32 = note_to_insert.onset_b32;
         let mut notes_starting_at_time = self.notes_starting_at_time(onset_b32);
 
         let mut note_found_at_index = None;
@@ -80,12 +206,6 @@ impl Score {
             return;
         }
 
-        let note_to_insert = Note {
-            pitch,
-            onset_b32,
-            duration_b32,
-        };
-
         match self.notes.get_mut(&onset_b32) {
             Some(notes_at_onset) => {
                 notes_at_onset.push(note_to_insert);
@@ -100,19 +220,12 @@ impl Score {
 
     // Creates a new Score with just notes between selection times and pitches.
     pub fn clone_at_selection(&self, selection_range: SelectionRange) -> Score {
-        let mut new_score = Score {
-            bpm: self.bpm,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut new_score = self.empty_copy();
 
-        for (&onset_b32, notes_at_onset) in &self.notes {
-            if onset_b32 >= selection_range.time_point_start_b32 && onset_b32 < selection_range.time_point_end_b32 {
-                for note in notes_at_onset {
-                    if note.pitch >= selection_range.pitch_low && note.pitch <= selection_range.pitch_high {
-                        // Assuming Pitch implements PartialOrd
-                        new_score.insert_or_remove(note.pitch, note.onset_b32, note.duration_b32);
-                    }
+        for notes_at_onset in self.notes.values() {
+            for note in notes_at_onset {
+                if selection_range.contains(note) {
+                    new_score.insert_or_remove_note(*note);
                 }
             }
         }
@@ -123,11 +236,7 @@ impl Score {
     pub fn translate(&self, time_point_start_b32: Option<u64>) -> Score {
         match time_point_start_b32 {
             Some(new_start_time) => {
-                let mut new_score = Score {
-                    bpm: self.bpm,
-                    notes: HashMap::new(),
-                    active_notes: HashMap::new(),
-                };
+                let mut new_score = self.empty_copy();
 
                 let mut min_onset = u64::MAX;
                 for (&onset_b32, _) in &self.notes {
@@ -153,7 +262,10 @@ impl Score {
                     };
 
                     for note in notes_at_onset {
-                        new_score.insert_or_remove(note.pitch, new_onset, note.duration_b32);
+                        new_score.insert_or_remove_note(Note {
+                            onset_b32: new_onset,
+                            ..*note
+                        });
                     }
                 }
 
@@ -164,6 +276,20 @@ impl Score {
     }
 
     pub fn insert(&mut self, pitch: Pitch, onset_b32: u64, duration_b32: u64) {
+        self.insert_note(Note {
+            pitch,
+            onset_b32,
+            duration_b32,
+            velocity: DEFAULT_VELOCITY,
+        });
+    }
+
+    // Inserts a note, merging it with overlapping notes of the same pitch. The merged
+    // note takes the velocity of the inserted one.
+    pub fn insert_note(&mut self, note_to_insert: Note) {
+        let pitch = note_to_insert.pitch;
+        let onset_b32 = note_to_insert.onset_b32;
+        let duration_b32 = note_to_insert.duration_b32;
         let end_b32 = onset_b32 + duration_b32;
         let mut overlapping_notes: Vec<(u64, Note)> = Vec::new();
 
@@ -188,6 +314,7 @@ impl Score {
                     self.notes.remove(onset);
                 }
             }
+            self.remove_active_note(*note);
         }
 
         // Calculate merged note boundaries
@@ -218,6 +345,7 @@ impl Score {
             pitch,
             onset_b32: merged_onset,
             duration_b32: merged_end - merged_onset,
+            velocity: note_to_insert.velocity,
         };
 
         match self.notes.get_mut(&merged_onset) {
@@ -235,15 +363,181 @@ impl Score {
     pub fn merge_down(&self, other: &Score) -> Score {
         let mut merged_score = self.clone();
 
-        for (&onset_b32, notes_at_onset) in &other.notes {
+        for notes_at_onset in other.notes.values() {
             for note in notes_at_onset {
-                merged_score.insert(note.pitch, onset_b32, note.duration_b32);
+                merged_score.insert_note(*note);
             }
         }
 
         merged_score
     }
 
+    // Pastes `buffer` where it stands, then `repeat - 1` more copies back to back.
+    pub fn paste(&self, buffer: &Score, mode: PasteMode, repeat: u64) -> Score {
+        let mut pasted_score = self.clone();
+        let Some(start) = buffer.notes.keys().min().copied() else {
+            return pasted_score;
+        };
+        let duration = buffer.duration();
+        let total_duration = duration * repeat;
+
+        match mode {
+            PasteMode::Merge => (),
+            PasteMode::Replace => {
+                let pitches = buffer.used_pitches();
+                pasted_score.delete_in_selection(SelectionRange {
+                    time_point_start_b32: start,
+                    time_point_end_b32: start + total_duration,
+                    pitch_low: pitches[0],
+                    pitch_high: pitches[pitches.len() - 1],
+                    mode: SelectionMode::Overlap,
+                });
+            }
+            PasteMode::Insert => pasted_score.shift_notes_from(start, total_duration),
+        }
+
+        for i in 0..repeat {
+            pasted_score = pasted_score.merge_down(&buffer.translate(Some(start + i * duration)));
+        }
+        pasted_score
+    }
+
+    // Moves every note starting at or after `time_point_b32` right by `delta_b32`.
+    pub fn shift_notes_from(&mut self, time_point_b32: u64, delta_b32: u64) {
+        let later_notes = self.take_selection(SelectionRange {
+            time_point_start_b32: time_point_b32,
+            time_point_end_b32: u64::MAX,
+            pitch_low: Pitch::new(Tone::C, 0),
+            pitch_high: Pitch::new(Tone::B, OCTAVE_MAX),
+            mode: SelectionMode::Onset,
+        });
+        for note in later_notes {
+            self.insert_note(Note {
+                onset_b32: note.onset_b32 + delta_b32,
+                ..note
+            });
+        }
+    }
+
+    // Opens up `length_b32` of silence at `at_b32`. Notes sounding across that point
+    // keep their length.
+    pub fn insert_time(&mut self, at_b32: u64, length_b32: u64) {
+        self.shift_notes_from(at_b32, length_b32);
+        self.set_arrangement(self.arrangement.insert_time(at_b32, length_b32));
+        for marker in &mut self.markers {
+            marker.time_b32 = ripple::after_insert(marker.time_b32, at_b32, length_b32);
+        }
+        self.length_b32 = self.length_b32.map(|end_b32| ripple::after_insert(end_b32, at_b32, length_b32));
+    }
+
+    // Removes the span and closes the gap. Notes starting in the span are deleted and
+    // notes sounding into it are shortened.
+    pub fn delete_time(&mut self, start_b32: u64, length_b32: u64) {
+        let notes: Vec<Note> = self.notes.values().flatten().copied().collect();
+        self.notes.clear();
+        self.active_notes.clear();
+        for note in notes {
+            if note.onset_b32 >= start_b32 && note.onset_b32 < start_b32 + length_b32 {
+                continue;
+            }
+            let onset_b32 = ripple::after_delete(note.onset_b32, start_b32, length_b32);
+            let end_b32 = ripple::after_delete(note.onset_b32 + note.duration_b32, start_b32, length_b32);
+            self.insert_note(Note {
+                onset_b32,
+                duration_b32: end_b32 - onset_b32,
+                ..note
+            });
+        }
+        self.set_arrangement(self.arrangement.delete_time(start_b32, length_b32));
+        // Markers in the span go with it, like the notes starting there
+        self.markers.retain(|marker| marker.time_b32 < start_b32 || marker.time_b32 >= start_b32 + length_b32);
+        for marker in &mut self.markers {
+            marker.time_b32 = ripple::after_delete(marker.time_b32, start_b32, length_b32);
+        }
+        self.length_b32 = self.length_b32.map(|end_b32| ripple::after_delete(end_b32, start_b32, length_b32).max(BAR_B32));
+    }
+
+    pub fn set_arrangement(&mut self, arrangement: Arrangement) {
+        self.arrangement = arrangement;
+        let mut rendered = self.empty_copy();
+        self.arrangement.render(&mut rendered);
+        self.clip_notes = rendered.notes;
+        self.clip_active_notes = rendered.active_notes;
+    }
+
+    // Moves the selected notes into a new pattern and plays them back from a clip at
+    // the bar where the selection starts. Returns the pattern name.
+    pub fn make_pattern(&mut self, selection_range: SelectionRange) -> Option<String> {
+        let notes = self.take_selection(selection_range);
+        let start_b32 = notes.iter().map(|note| note.onset_b32).min()?;
+        let start_b32 = start_b32 - start_b32 % BAR_B32;
+        let end_b32 = notes.iter().map(|note| note.onset_b32 + note.duration_b32).max()?;
+
+        let mut pattern_score = Score::new(self.bpm);
+        for note in notes {
+            pattern_score.insert_note(Note {
+                onset_b32: note.onset_b32 - start_b32,
+                ..note
+            });
+        }
+        let name = self.arrangement.next_pattern_name();
+        let pattern = Pattern {
+            name: name.clone(),
+            length_b32: (end_b32 - start_b32).div_ceil(BAR_B32) * BAR_B32,
+            score: pattern_score,
+        };
+        self.set_arrangement(
+            self.arrangement
+                .with_pattern(pattern)
+                .with_clip(Clip::new(&name, start_b32)),
+        );
+        Some(name)
+    }
+
+    // Bakes the clips into plain notes. The patterns stay for later use.
+    pub fn flatten(&mut self) {
+        let clip_notes: Vec<Note> = self.clip_notes.values().flatten().copied().collect();
+        for note in clip_notes {
+            self.insert_note(note);
+        }
+        self.set_arrangement(self.arrangement.without_clips());
+    }
+
+    // Adds a marker, or renames the one already at `time_b32`.
+    pub fn set_marker(&mut self, time_b32: u64, name: &str) {
+        match self.markers.binary_search_by_key(&time_b32, |marker| marker.time_b32) {
+            Ok(index) => self.markers[index].name = name.to_string(),
+            Err(index) => self.markers.insert(index, Marker::new(time_b32, name)),
+        }
+    }
+
+    pub fn remove_marker(&mut self, time_b32: u64) {
+        self.markers.retain(|marker| marker.time_b32 != time_b32);
+    }
+
+    pub fn marker_at(&self, time_b32: u64) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.time_b32 == time_b32)
+    }
+
+    pub fn next_marker(&self, time_b32: u64) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.time_b32 > time_b32)
+    }
+
+    pub fn previous_marker(&self, time_b32: u64) -> Option<&Marker> {
+        self.markers.iter().rev().find(|marker| marker.time_b32 < time_b32)
+    }
+
+    // Start and end of the section holding `time_b32`: from the marker at or before it to
+    // the next marker, or for the last section to the end of the song's last bar.
+    pub fn section_at(&self, time_b32: u64) -> Option<(u64, u64)> {
+        let start_b32 = self.markers.iter().rev().find(|marker| marker.time_b32 <= time_b32)?.time_b32;
+        let end_b32 = match self.next_marker(start_b32) {
+            Some(marker) => marker.time_b32,
+            None => self.end_time_b32().div_ceil(BAR_B32).max(start_b32 / BAR_B32 + 1) * BAR_B32,
+        };
+        Some((start_b32, end_b32))
+    }
+
     pub fn duration(&self) -> u64 {
         if self.notes.is_empty() {
             return 0; // Return 0 if the score is empty
@@ -267,6 +561,65 @@ impl Score {
         last_final_time - first_onset
     }
 
+    // Applies `transform` to every note in the selection, re-inserting the results with
+    // the usual merge rules.
+    pub fn transform_selection(&mut self, selection_range: SelectionRange, transform: Transform) {
+        let selected_notes = self.take_selection(selection_range);
+        let span_end_b32 = selected_notes
+            .iter()
+            .map(|note| note.onset_b32 + note.duration_b32)
+            .max()
+            .unwrap_or(0)
+            .max(selection_range.time_point_end_b32);
+
+        for note in selected_notes {
+            let new_note = transform.apply(note, selection_range, span_end_b32, self.key);
+            self.insert_note(new_note);
+        }
+    }
+
+    // Removes the notes in the selection and returns them.
+    pub fn take_selection(&mut self, selection_range: SelectionRange) -> Vec<Note> {
+        let selected_notes: Vec<Note> = self
+            .clone_at_selection(selection_range)
+            .notes
+            .into_values()
+            .flatten()
+            .collect();
+        self.delete_in_selection(selection_range);
+        selected_notes
+    }
+
+    // The note of `pitch` sounding at `time_point_b32`, if any.
+    // Clip notes are left out, they are edited through their pattern.
+    pub fn note_at(&self, pitch: Pitch, time_point_b32: u64) -> Option<Note> {
+        self.active_notes
+            .get(&time_point_b32)?
+            .iter()
+            .find(|active_note| active_note.note.pitch == pitch)
+            .map(|active_note| active_note.note)
+    }
+
+    // Selection covering every note, or None for an empty score.
+    pub fn full_range(&self) -> Option<SelectionRange> {
+        let pitches = self.used_pitches();
+        Some(SelectionRange {
+            time_point_start_b32: 0,
+            time_point_end_b32: self.end_time_b32(),
+            pitch_low: *pitches.first()?,
+            pitch_high: *pitches.last()?,
+            mode: SelectionMode::Onset,
+        })
+    }
+
+    fn remove_active_note(&mut self, note: Note) {
+        for t in note.onset_b32..note.onset_b32 + note.duration_b32 {
+            if let Some(notes) = self.active_notes.get_mut(&t) {
+                notes.retain(|active| active.note.pitch != note.pitch);
+            }
+        }
+    }
+
     // Helper method to update active_notes when inserting/removing notes
     fn update_active_notes(&mut self, note: Note) {
         // Add new entries
@@ -303,10 +656,11 @@ impl Score {
 
     // New method to get active notes at a specific time
     pub fn notes_active_at_time(&self, time_point_b32: u64) -> Vec<ActiveNote> {
-        let result = self.active_notes
+        let mut result = self.active_notes
             .get(&time_point_b32)
             .cloned()
             .unwrap_or_default();
+        result.extend(self.clip_active_notes.get(&time_point_b32).into_iter().flatten().cloned());
         result
     }
 
@@ -320,20 +674,21 @@ impl Score {
 
         // Identify notes to remove and keep
         for (&onset_b32, notes_at_onset) in &self.notes {
-            if onset_b32 >= selection_range.time_point_start_b32 && onset_b32 < selection_range.time_point_end_b32 {
-                let (keep, remove): (Vec<Note>, Vec<Note>) = notes_at_onset
-                    .iter()
-                    .cloned()
-                    .partition(|note| note.pitch < selection_range.pitch_low || note.pitch > selection_range.pitch_high);
+            let (keep, remove): (Vec<Note>, Vec<Note>) = notes_at_onset
+                .iter()
+                .cloned()
+                .partition(|note| !selection_range.contains(note));
+            if remove.is_empty() {
+                continue;
+            }
 
-                debug!("At onset {}: keeping {} notes, removing {} notes", 
-                    onset_b32, keep.len(), remove.len());
+            debug!("At onset {}: keeping {} notes, removing {} notes", 
+                onset_b32, keep.len(), remove.len());
 
-                if !keep.is_empty() {
-                    notes_to_keep.insert(onset_b32, keep);
-                } else {
-                    onsets_to_remove.push(onset_b32);
-                }
+            if !keep.is_empty() {
+                notes_to_keep.insert(onset_b32, keep);
+            } else {
+                onsets_to_remove.push(onset_b32);
             }
         }
 
@@ -371,13 +726,12 @@ impl Score {
 
 mod tests {
     use super::*;
+    use crate::quantize::{Humanize, Quantize, QuantizeTarget};
+    use crate::resolution::Resolution;
+    use crate::scale::Scale;
 
     fn create_test_score() -> Score {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
         // Add some test notes
         score.insert(Pitch::new(Tone::C, 4), 0, 32); // C4 (MIDI 60)
         score.insert(Pitch::new(Tone::E, 4), 32, 32); // E4 (MIDI 64)
@@ -404,17 +758,15 @@ mod tests {
         assert!(score.time_within_song(0));
         assert!(score.time_within_song(64));
         assert!(score.time_within_song(95));
-        assert!(!score.time_within_song(96)); // Last note ends at 96
+        // Last note ends at 96, then a bar of tail
+        assert!(score.time_within_song(96));
+        assert!(score.time_within_song(127));
         assert!(!score.time_within_song(128));
     }
 
     #[test]
     fn test_insert_or_remove() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Test insertion
         score.insert_or_remove(Pitch::new(Tone::C, 4), 0, 32);
@@ -434,6 +786,7 @@ mod tests {
             time_point_end_b32: 64,
             pitch_low: Pitch::new(Tone::C, 4),
             pitch_high: Pitch::new(Tone::E, 4),
+            mode: SelectionMode::Onset,
         };
 
         let selected = score.clone_at_selection(selection_range);
@@ -462,11 +815,7 @@ mod tests {
 
     #[test]
     fn test_insert() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Test basic insertion
         score.insert(Pitch::new(Tone::C, 4), 0, 32);
@@ -481,18 +830,10 @@ mod tests {
 
     #[test]
     fn test_merge_down() {
-        let mut score1 = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score1 = Score::new(120);
         score1.insert(Pitch::new(Tone::C, 4), 0, 32);
 
-        let mut score2 = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score2 = Score::new(120);
         score2.insert(Pitch::new(Tone::E, 4), 0, 32);
 
         let merged = score1.merge_down(&score2);
@@ -501,24 +842,348 @@ mod tests {
 
     #[test]
     fn test_duration() {
-        let empty_score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let empty_score = Score::new(120);
         assert_eq!(empty_score.duration(), 0);
 
         let score = create_test_score();
         assert_eq!(score.duration(), 96); // From start of first note to end of last note
     }
 
+    #[test]
+    fn test_used_pitches() {
+        let mut score = create_test_score();
+        score.insert(Pitch::new(Tone::C, 4), 96, 8);
+        score.insert(Pitch::new(Tone::B, 3), 96, 8);
+
+        assert_eq!(
+            score.used_pitches(),
+            vec![
+                Pitch::new(Tone::B, 3),
+                Pitch::new(Tone::C, 4),
+                Pitch::new(Tone::E, 4),
+                Pitch::new(Tone::G, 4)
+            ]
+        );
+    }
+
+    fn selection_range(start: u64, end: u64, low: Pitch, high: Pitch) -> SelectionRange {
+        SelectionRange {
+            time_point_start_b32: start,
+            time_point_end_b32: end,
+            pitch_low: low,
+            pitch_high: high,
+            mode: SelectionMode::Onset,
+        }
+    }
+
+    #[test]
+    fn test_insert_merge_clears_replaced_active_notes() {
+        let mut score = Score::new(120);
+        score.insert(Pitch::new(Tone::C, 4), 8, 8);
+        score.insert(Pitch::new(Tone::C, 4), 0, 12);
+
+        let notes_at_10 = score.notes_active_at_time(10);
+        assert_eq!(notes_at_10.len(), 1);
+        assert_eq!(notes_at_10[0].note.onset_b32, 0);
+        assert_eq!(notes_at_10[0].note.duration_b32, 16);
+    }
+
+    #[test]
+    fn test_transform_transpose() {
+        let mut score = create_test_score();
+        let range = selection_range(0, 64, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+
+        score.transform_selection(range, Transform::Transpose(2));
+        assert_eq!(score.notes_starting_at_time(0)[0].pitch, Pitch::new(Tone::D, 4));
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::Fs, 4));
+        // Outside the selected time range
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::G, 4));
+    }
+
+    #[test]
+    fn test_transform_diatonic() {
+        let mut score = create_test_score();
+        score.key = Key::new(Tone::C, Scale::Major);
+        let range = selection_range(0, 96, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+
+        score.transform_selection(range, Transform::TransposeDiatonic(2));
+        assert_eq!(score.notes_starting_at_time(0)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::G, 4));
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::B, 4));
+    }
+
+    #[test]
+    fn test_transform_invert() {
+        let mut score = create_test_score();
+        let range = selection_range(0, 96, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+
+        score.transform_selection(range, Transform::Invert);
+        assert_eq!(score.notes_starting_at_time(0)[0].pitch, Pitch::new(Tone::G, 4));
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::Ds, 4));
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::C, 4));
+    }
+
+    #[test]
+    fn test_transform_retrograde() {
+        let mut score = create_test_score();
+        let range = selection_range(0, 96, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+
+        score.transform_selection(range, Transform::Retrograde);
+        assert_eq!(score.notes_starting_at_time(0)[0].pitch, Pitch::new(Tone::G, 4));
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::C, 4));
+    }
+
+    #[test]
+    fn test_transform_stretch() {
+        let mut score = create_test_score();
+        let range = selection_range(0, 96, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+
+        score.transform_selection(range, Transform::Stretch(1, 2));
+        assert_eq!(score.notes_starting_at_time(16)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(score.notes_starting_at_time(16)[0].duration_b32, 16);
+        assert_eq!(score.duration(), 48);
+    }
+
+    #[test]
+    fn test_transform_quantize() {
+        let mut score = Score::new(120);
+        score.insert(Pitch::new(Tone::C, 4), 1, 5);
+        score.insert(Pitch::new(Tone::E, 4), 15, 8);
+        let range = score.full_range().unwrap();
+
+        let mut quantize = Quantize::new(Resolution::Time1_8);
+        quantize.target = QuantizeTarget::Both;
+        score.transform_selection(range, Transform::Quantize(quantize));
+        assert_eq!(score.notes_starting_at_time(0)[0].duration_b32, 4);
+        assert_eq!(score.notes_starting_at_time(16)[0].pitch, Pitch::new(Tone::E, 4));
+
+        // Swing delays every second grid point
+        let mut score = Score::new(120);
+        score.insert(Pitch::new(Tone::C, 4), 4, 4);
+        score.insert(Pitch::new(Tone::E, 4), 12, 4);
+        let quantize = Quantize::new(Resolution::Time1_8).next_swing().next_swing();
+        score.transform_selection(score.full_range().unwrap(), Transform::Quantize(quantize));
+        assert_eq!(score.notes_starting_at_time(6)[0].pitch, Pitch::new(Tone::C, 4));
+        assert_eq!(score.notes_starting_at_time(14)[0].pitch, Pitch::new(Tone::E, 4));
+
+        // Half strength moves half way to the grid
+        let mut score = Score::new(120);
+        score.insert(Pitch::new(Tone::C, 4), 6, 4);
+        let quantize = Quantize::new(Resolution::Time1_4).next_strength().next_strength();
+        score.transform_selection(score.full_range().unwrap(), Transform::Quantize(quantize));
+        assert_eq!(score.notes_starting_at_time(7)[0].pitch, Pitch::new(Tone::C, 4));
+    }
+
+    #[test]
+    fn test_transform_humanize() {
+        let humanize = Humanize::new();
+        let mut first = create_test_score();
+        let mut second = create_test_score();
+        first.transform_selection(first.full_range().unwrap(), Transform::Humanize(humanize));
+        second.transform_selection(second.full_range().unwrap(), Transform::Humanize(humanize));
+
+        let mut first_notes: Vec<Note> = first.notes.values().flatten().copied().collect();
+        let mut second_notes: Vec<Note> = second.notes.values().flatten().copied().collect();
+        first_notes.sort_by_key(|note| note.pitch.index());
+        second_notes.sort_by_key(|note| note.pitch.index());
+        assert_eq!(first_notes.len(), 3);
+        for (note, other) in first_notes.iter().zip(&second_notes) {
+            assert_eq!(note.onset_b32, other.onset_b32);
+            assert_eq!(note.velocity, other.velocity);
+            assert!(note.velocity.abs_diff(DEFAULT_VELOCITY) <= humanize.velocity_range);
+        }
+        assert!(first_notes[1].onset_b32.abs_diff(32) <= humanize.onset_range_b32);
+    }
+
+    #[test]
+    fn test_groove_delays() {
+        let mut score = create_test_score();
+        assert_eq!(score.groove.delay_b32_hundredths(2, 0), 0);
+
+        score.groove = score.groove.next_swing().next_swing();
+        assert_eq!(score.groove.delay_b32_hundredths(0, 0), 0);
+        assert_eq!(score.groove.delay_b32_hundredths(2, 0), 20);
+        assert_eq!(score.groove.delay_b32_hundredths(3, 0), 0);
+
+        score.groove = score.groove.toggle_swing_unit().set_track_swing(0, Some(50));
+        assert_eq!(score.groove.delay_b32_hundredths(2, 0), 0);
+        assert_eq!(score.groove.delay_b32_hundredths(4, 0), 200);
+        assert_eq!(score.groove.delay_b32_hundredths(4, 1), 40);
+
+        score.groove = score.groove.next_template();
+        assert_eq!(score.groove.delay_b32_hundredths(8, 1), 40);
+        assert_eq!(score.groove.delay_b32_hundredths(12, 1), 40);
+    }
+
+    #[test]
+    fn test_selection_modes() {
+        let mut score = create_test_score();
+        let onset_range = selection_range(40, 80, Pitch::new(Tone::C, 4), Pitch::new(Tone::G, 4));
+        let overlap_range = onset_range.with_mode(SelectionMode::Overlap);
+
+        assert_eq!(score.clone_at_selection(onset_range).notes.len(), 1);
+        let overlapping = score.clone_at_selection(overlap_range);
+        assert_eq!(overlapping.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(overlapping.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::G, 4));
+
+        score.delete_in_selection(overlap_range);
+        assert!(score.notes_starting_at_time(32).is_empty());
+        assert!(score.notes_starting_at_time(64).is_empty());
+        assert_eq!(score.notes_starting_at_time(0).len(), 1);
+    }
+
+    #[test]
+    fn test_delete_single_note() {
+        let mut score = create_test_score();
+        let note = score.note_at(Pitch::new(Tone::E, 4), 50).unwrap();
+        score.delete_in_selection(SelectionRange::for_note(note));
+
+        assert!(score.note_at(Pitch::new(Tone::E, 4), 50).is_none());
+        assert!(score.notes_active_at_time(40).is_empty());
+        assert_eq!(score.notes_starting_at_time(64).len(), 1);
+    }
+
+    #[test]
+    fn test_paste_modes() {
+        let score = create_test_score();
+        let mut buffer = Score::new(120);
+        buffer.insert(Pitch::new(Tone::D, 4), 40, 8);
+        buffer.insert(Pitch::new(Tone::F, 4), 48, 8);
+
+        let merged = score.paste(&buffer, PasteMode::Merge, 1);
+        assert_eq!(merged.notes_starting_at_time(32).len(), 1);
+        assert_eq!(merged.notes_starting_at_time(40).len(), 1);
+
+        // Clears E4, which sounds inside the D4 to F4 destination range
+        let replaced = score.paste(&buffer, PasteMode::Replace, 1);
+        assert!(replaced.notes_starting_at_time(32).is_empty());
+        assert_eq!(replaced.notes_starting_at_time(0).len(), 1);
+        assert_eq!(replaced.notes_starting_at_time(64).len(), 1);
+
+        // Two copies shift G4 right by twice the buffer duration
+        let inserted = score.paste(&buffer, PasteMode::Insert, 2);
+        assert_eq!(inserted.notes_starting_at_time(56)[0].pitch, Pitch::new(Tone::D, 4));
+        assert_eq!(inserted.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::F, 4));
+        assert_eq!(inserted.notes_starting_at_time(96)[0].pitch, Pitch::new(Tone::G, 4));
+        assert_eq!(inserted.notes_starting_at_time(32)[0].duration_b32, 32);
+    }
+
+    #[test]
+    fn test_insert_and_delete_time() {
+        let mut score = create_test_score();
+        score.insert_time(32, 32);
+        assert_eq!(score.notes_starting_at_time(0).len(), 1);
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(score.notes_starting_at_time(96)[0].pitch, Pitch::new(Tone::G, 4));
+
+        score.delete_time(32, 32);
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::E, 4));
+        assert_eq!(score.notes_starting_at_time(64)[0].pitch, Pitch::new(Tone::G, 4));
+
+        // E4 is deleted, C4 is cut short at the start of the span
+        score.delete_time(16, 32);
+        assert_eq!(score.notes_starting_at_time(0)[0].duration_b32, 16);
+        assert_eq!(score.notes_starting_at_time(32)[0].pitch, Pitch::new(Tone::G, 4));
+        assert!(score.note_at(Pitch::new(Tone::E, 4), 20).is_none());
+        assert_eq!(score.notes_active_at_time(40)[0].note.pitch, Pitch::new(Tone::G, 4));
+    }
+
+    #[test]
+    fn test_patterns_and_flatten() {
+        let mut score = create_test_score();
+        let name = score.make_pattern(selection_range(0, 64, Pitch::new(Tone::C, 4), Pitch::new(Tone::E, 4)));
+        assert_eq!(name.as_deref(), Some("P1"));
+        assert_eq!(score.arrangement.pattern("P1").unwrap().length_b32, 64);
+        // The clip plays the notes, but they are no longer editable in place
+        assert!(score.notes_starting_at_time(0).is_empty());
+        assert_eq!(score.sounding_notes_starting_at_time(0)[0].pitch, Pitch::new(Tone::C, 4));
+        assert!(score.note_at(Pitch::new(Tone::C, 4), 0).is_none());
+
+        score.set_arrangement(score.arrangement.update_clip_at(0, |clip| clip.change_repeat(1).change_transpose(2)));
+        assert_eq!(score.end_time_b32(), 128);
+        assert_eq!(score.sounding_notes_starting_at_time(64).len(), 2); // D4 from the clip, G4
+        assert_eq!(score.sounding_notes_starting_at_time(96)[0].pitch, Pitch::new(Tone::Fs, 4));
+
+        // Editing the pattern changes every repeat
+        let mut pattern = score.arrangement.pattern("P1").unwrap().clone();
+        pattern.score.insert(Pitch::new(Tone::C, 5), 0, 8);
+        score.set_arrangement(score.arrangement.with_pattern(pattern));
+        assert_eq!(score.sounding_notes_starting_at_time(0).len(), 2);
+        assert_eq!(score.sounding_notes_starting_at_time(64).len(), 3);
+
+        score.flatten();
+        assert!(score.arrangement.clips.is_empty());
+        assert_eq!(score.arrangement.patterns.len(), 1);
+        assert_eq!(score.notes_starting_at_time(64).len(), 3);
+        assert_eq!(score.note_at(Pitch::new(Tone::Fs, 4), 100).unwrap().onset_b32, 96);
+    }
+
+    #[test]
+    fn test_markers_and_sections() {
+        let mut score = Score::new(120);
+        score.insert_note(Note {
+            pitch: Pitch::new(Tone::C, 4),
+            onset_b32: 0,
+            duration_b32: 100,
+            velocity: DEFAULT_VELOCITY,
+        });
+        score.set_marker(32, "Verse");
+        score.set_marker(0, "Intro");
+        score.set_marker(32, "Pre-Chorus");
+        assert_eq!(score.markers.len(), 2);
+        assert_eq!(score.marker_at(32).unwrap().name, "Pre-Chorus");
+
+        assert_eq!(score.next_marker(0).unwrap().time_b32, 32);
+        assert_eq!(score.previous_marker(32).unwrap().time_b32, 0);
+        assert!(score.next_marker(32).is_none());
+        // The last section runs to the end of the last bar with a note
+        assert_eq!(score.section_at(10), Some((0, 32)));
+        assert_eq!(score.section_at(40), Some((32, 128)));
+
+        score.insert_time(0, 32);
+        assert_eq!(score.marker_at(64).unwrap().name, "Pre-Chorus");
+        score.delete_time(32, 32);
+        assert_eq!(score.markers.len(), 1);
+        assert_eq!(score.section_at(0), None);
+    }
+
+    #[test]
+    fn test_song_length() {
+        let mut score = Score::new(120);
+        assert_eq!(score.song_length_b32(), 32);
+        score.insert(Pitch::new(Tone::C, 4), 40, 8);
+        assert_eq!(score.song_length_b32(), 96);
+        score.length_b32 = Some(48);
+        score.insert_time(0, 32);
+        assert_eq!(score.song_length_b32(), 80);
+        score.delete_time(0, 64);
+        assert_eq!(score.song_length_b32(), 32);
+    }
+
+    #[test]
+    fn test_notes_out_of_key() {
+        let mut score = create_test_score();
+        score.key = Key::new(Tone::D, Scale::Major);
+        score.insert(Pitch::new(Tone::Fs, 4), 96, 8);
+
+        let out_of_key = score.notes_out_of_key();
+        assert_eq!(out_of_key.len(), 1);
+        assert_eq!(out_of_key[0].pitch, Pitch::new(Tone::C, 4));
+        assert_eq!(out_of_key[0].onset_b32, 0);
+    }
+
+    #[test]
+    fn test_onsets_per_bar() {
+        let mut score = create_test_score();
+        score.insert(Pitch::new(Tone::G, 4), 8, 8);
+
+        assert_eq!(score.onsets_per_bar(), vec![2, 1, 1]);
+    }
+
     #[test]
     fn test_note_states() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Add a note from time 0 to 32
         score.insert(Pitch::new(Tone::C, 4), 0, 32);
@@ -548,11 +1213,7 @@ mod tests {
 
     #[test]
     fn test_overlapping_notes() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Add two overlapping notes of the same pitch
         score.insert(Pitch::new(Tone::C, 4), 0, 32);
@@ -575,11 +1236,7 @@ mod tests {
 
     #[test]
     fn test_remove_note() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Add and then remove a note
         score.insert_or_remove(Pitch::new(Tone::C, 4), 0, 32);
@@ -598,11 +1255,7 @@ mod tests {
 
     #[test]
     fn test_multiple_pitches() {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+        let mut score = Score::new(120);
 
         // Add two notes at different pitches at the same time
         score.insert(Pitch::new(Tone::C, 4), 0, 32);
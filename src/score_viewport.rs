@@ -1,44 +1,125 @@
 use crate::draw_components::ViewportDrawResult;
-use crate::pitch::Pitch;
+use crate::pitch::{Pitch, OCTAVE_MAX};
 use crate::resolution::Resolution;
+use crate::zoom::Zoom;
 use std::fmt;
 
+// How the view keeps up with the playhead during playback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowMode {
+    Page,       // Jumps a page when the playhead leaves the view
+    Continuous, // Scrolls along, keeping the playhead a quarter of the way in
+    Off,
+}
+
+impl FollowMode {
+    pub fn next(&self) -> FollowMode {
+        match self {
+            FollowMode::Page => FollowMode::Continuous,
+            FollowMode::Continuous => FollowMode::Off,
+            FollowMode::Off => FollowMode::Page,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FollowMode::Page => "page",
+            FollowMode::Continuous => "scroll",
+            FollowMode::Off => "off",
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct ScoreViewport {
     pub middle_pitch: Pitch,
     pub resolution: Resolution,
+    pub zoom: Zoom,
     pub time_point: u64,
     pub playback_time_point: u64,
+    pub folded: bool, // Only show pitch rows that have notes
+    pub follow: FollowMode,
 }
 
 impl ScoreViewport {
     pub fn new(
         middle_pitch: Pitch,
         resolution: Resolution,
+        zoom: Zoom,
         time_point: u64,
         playback_time_point: u64,
     ) -> ScoreViewport {
         ScoreViewport {
             middle_pitch,
             resolution,
+            zoom,
             time_point,
             playback_time_point,
+            folded: false,
+            follow: FollowMode::Page,
         }
     }
 
     pub fn next_octave(&self) -> ScoreViewport {
+        self.scroll_semitones(12)
+    }
+
+    pub fn prev_octave(&self) -> ScoreViewport {
+        self.scroll_semitones(-12)
+    }
+
+    pub fn next_semitone(&self) -> ScoreViewport {
+        self.scroll_semitones(1)
+    }
+
+    pub fn prev_semitone(&self) -> ScoreViewport {
+        self.scroll_semitones(-1)
+    }
+
+    // Moves the middle pitch, stopping at the lowest/highest pitch instead of failing.
+    fn scroll_semitones(&self, semitones: i32) -> ScoreViewport {
         let mut new_viewport = *self;
-        if let Some(next_pitch) = self.middle_pitch.next() {
-            new_viewport.middle_pitch = next_pitch;
+        let highest = (OCTAVE_MAX + 1) * 12 - 1;
+        let index = (self.middle_pitch.index() as i32 + semitones).clamp(0, highest as i32);
+        if let Some(pitch) = Pitch::from_index(index as u16) {
+            new_viewport.middle_pitch = pitch;
         }
         new_viewport
     }
 
-    pub fn prev_octave(&self) -> ScoreViewport {
+    // Centers the view on the pitch range spanned by `pitches`.
+    pub fn fit_to_pitches(&self, pitches: &[Pitch]) -> ScoreViewport {
         let mut new_viewport = *self;
-        if let Some(prev_pitch) = self.middle_pitch.prev() {
-            new_viewport.middle_pitch = prev_pitch;
+        let low = pitches.iter().map(|pitch| pitch.index()).min();
+        let high = pitches.iter().map(|pitch| pitch.index()).max();
+        if let (Some(low), Some(high)) = (low, high) {
+            if let Some(pitch) = Pitch::from_index((low + high).div_ceil(2)) {
+                new_viewport.middle_pitch = pitch;
+            }
+        }
+        new_viewport
+    }
+
+    // Scrolls just far enough for `pitch` to be inside the last drawn pitch range.
+    pub fn scroll_to_pitch(&self, pitch: Pitch, viewport_draw_result: &ViewportDrawResult) -> ScoreViewport {
+        if self.folded {
+            return *self;
         }
+        let index = pitch.index() as i32;
+        let low = viewport_draw_result.pitch_low.index() as i32;
+        let high = viewport_draw_result.pitch_high.index() as i32;
+        if index > high {
+            self.scroll_semitones(index - high)
+        } else if index < low {
+            self.scroll_semitones(index - low)
+        } else {
+            *self
+        }
+    }
+
+    pub fn toggle_fold(&self) -> ScoreViewport {
+        let mut new_viewport = *self;
+        new_viewport.folded = !self.folded;
         new_viewport
     }
 
@@ -78,12 +159,46 @@ impl ScoreViewport {
         new_viewport
     }
 
+    pub fn zoom_in(&self) -> ScoreViewport {
+        let mut new_viewport = *self;
+        new_viewport.zoom = self.zoom.zoom_in();
+        new_viewport
+    }
+
+    pub fn zoom_out(&self) -> ScoreViewport {
+        let mut new_viewport = *self;
+        new_viewport.zoom = self.zoom.zoom_out();
+        new_viewport
+    }
+
     pub fn set_playback_time(&self, time: u64) -> ScoreViewport {
         let mut new_viewport = *self;
         new_viewport.playback_time_point = time;
         new_viewport
     }
 
+    pub fn next_follow(&self) -> ScoreViewport {
+        let mut new_viewport = *self;
+        new_viewport.follow = self.follow.next();
+        new_viewport
+    }
+
+    // Moves the view to keep the playhead at `time` in sight, as the follow mode says.
+    pub fn follow_playhead(&self, time: u64, viewport_draw_result: &ViewportDrawResult) -> ScoreViewport {
+        let start = viewport_draw_result.time_point_start;
+        let end = viewport_draw_result.time_point_end;
+        match self.follow {
+            FollowMode::Page if time < start || time >= end => self.set_time_point(time - time % 32),
+            FollowMode::Continuous => {
+                // Whole columns, so notes don't shift between them as the view scrolls
+                let col_duration = self.zoom.duration_b32();
+                let time_point = time.saturating_sub((end - start) / 4);
+                self.set_time_point(time_point - time_point % col_duration)
+            }
+            _ => *self,
+        }
+    }
+
     pub fn set_time_point(&self, time: u64) -> ScoreViewport {
         let mut new_viewport = *self;
         new_viewport.time_point = time;
@@ -100,3 +215,29 @@ impl fmt::Display for ScoreViewport {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::Tone;
+
+    #[test]
+    fn test_follow_playhead() {
+        let viewport = ScoreViewport::new(Pitch::new(Tone::C, 4), Resolution::Time1_16, Zoom::Time1_16, 32, 0);
+        let drawn = ViewportDrawResult {
+            pitch_low: Pitch::new(Tone::C, 3),
+            pitch_high: Pitch::new(Tone::C, 5),
+            time_point_start: 32,
+            time_point_end: 96,
+        };
+        // Page mode only jumps once the playhead leaves the view, to the start of its bar
+        assert_eq!(viewport.follow_playhead(64, &drawn).time_point, 32);
+        assert_eq!(viewport.follow_playhead(100, &drawn).time_point, 96);
+        // Continuous scrolling keeps the playhead a quarter of the way in, on a column
+        let viewport = viewport.next_follow();
+        assert_eq!(viewport.follow, FollowMode::Continuous);
+        assert_eq!(viewport.follow_playhead(64, &drawn).time_point, 48);
+        assert_eq!(viewport.follow_playhead(69, &drawn).time_point, 52);
+        assert_eq!(viewport.next_follow().follow_playhead(200, &drawn).time_point, 32);
+    }
+}
@@ -1,5 +1,43 @@
 use crate::score::Score;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasteMode {
+    Merge,   // Merge with overlapping notes of the same pitch
+    Replace, // Clear the destination range first
+    Insert,  // Shift later notes right to make room
+}
+
+impl PasteMode {
+    pub fn next(&self) -> PasteMode {
+        match self {
+            PasteMode::Merge => PasteMode::Replace,
+            PasteMode::Replace => PasteMode::Insert,
+            PasteMode::Insert => PasteMode::Merge,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            PasteMode::Merge => "merge",
+            PasteMode::Replace => "replace",
+            PasteMode::Insert => "insert",
+        }
+    }
+}
+
+pub fn next_paste_repeat(repeat: u64) -> u64 {
+    match repeat {
+        1 => 2,
+        2 => 3,
+        3 => 4,
+        4 => 8,
+        8 => 16,
+        _ => 1,
+    }
+}
+
+// Score carries the arrangement layers, but there is only ever one buffer.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum SelectionBuffer {
     None,
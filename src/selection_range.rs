@@ -1,4 +1,27 @@
 use crate::pitch::Pitch;
+use crate::score::Note;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionMode {
+    Onset,   // Notes starting inside the time range
+    Overlap, // Notes sounding anywhere inside the time range
+}
+
+impl SelectionMode {
+    pub fn toggle(&self) -> SelectionMode {
+        match self {
+            SelectionMode::Onset => SelectionMode::Overlap,
+            SelectionMode::Overlap => SelectionMode::Onset,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SelectionMode::Onset => "onset",
+            SelectionMode::Overlap => "overlap",
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct SelectionRange {
@@ -6,4 +29,39 @@ pub struct SelectionRange {
     pub time_point_end_b32: u64,
     pub pitch_low: Pitch,
     pub pitch_high: Pitch,
-}
\ No newline at end of file
+    pub mode: SelectionMode,
+}
+
+impl SelectionRange {
+    // Selection holding just `note`.
+    pub fn for_note(note: Note) -> SelectionRange {
+        SelectionRange {
+            time_point_start_b32: note.onset_b32,
+            time_point_end_b32: note.onset_b32 + 1,
+            pitch_low: note.pitch,
+            pitch_high: note.pitch,
+            mode: SelectionMode::Onset,
+        }
+    }
+
+    pub fn with_mode(&self, mode: SelectionMode) -> SelectionRange {
+        let mut selection_range = *self;
+        selection_range.mode = mode;
+        selection_range
+    }
+
+    pub fn contains(&self, note: &Note) -> bool {
+        if note.pitch < self.pitch_low || note.pitch > self.pitch_high {
+            return false;
+        }
+        match self.mode {
+            SelectionMode::Onset => {
+                note.onset_b32 >= self.time_point_start_b32 && note.onset_b32 < self.time_point_end_b32
+            }
+            SelectionMode::Overlap => {
+                note.onset_b32 < self.time_point_end_b32
+                    && note.onset_b32 + note.duration_b32 > self.time_point_start_b32
+            }
+        }
+    }
+}
@@ -2,16 +2,11 @@
 
 use crate::pitch::{Pitch, Tone};
 use crate::score::{Note, Score, NoteState};
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
 pub fn create_song() -> Score {
-    let mut score = Score {
-        bpm: 120, // Default BPM
-        notes: HashMap::new(),
-        active_notes: HashMap::new(),
-    };
+    let mut score = Score::new(120); // Default BPM
 
     let file = File::open("song.txt").expect("Could not open song.txt");
     let reader = BufReader::new(file);
@@ -1,14 +1,19 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::PathBuf;
 use chrono::Local;
 use std::io::BufRead;
 use std::io::BufReader;
-use std::collections::HashMap;
 
-use crate::score::Score;
+use crate::arrangement::{Arrangement, Clip, Pattern};
+use crate::score::{Note, Score, DEFAULT_VELOCITY};
 use crate::pitch::Tone;
 use crate::pitch::Pitch;
+use crate::groove::{Groove, GrooveTemplate, SwingUnit};
+use crate::loop_state::{LoopMode, LoopState};
+use crate::marker::Marker;
+use crate::midi_file;
+use crate::scale::{Key, Scale};
 
 pub struct SongFile {
     current_path: Option<PathBuf>,
@@ -26,7 +31,7 @@ impl SongFile {
         PathBuf::from(format!("song_{}.txt", date))
     }
 
-    pub fn save(&mut self, score: &Score) -> io::Result<()> {
+    pub fn save(&mut self, score: &Score, loop_state: &LoopState) -> io::Result<()> {
         let path = self.current_path.clone()
             .unwrap_or_else(|| self.generate_default_filename());
         
@@ -34,103 +39,385 @@ impl SongFile {
         
         // Write BPM
         writeln!(file, "BPM: {}", score.bpm)?;
-        
-        // Write notes
-        let mut sorted_times: Vec<_> = score.notes.keys().collect();
-        sorted_times.sort();
-        
-        for &time in sorted_times {
-            if let Some(notes) = score.notes.get(&time) {
-                let mut note_strs = Vec::new();
-                
-                for note in notes {
-                    let tone_str = match note.pitch.tone {
-                        Tone::C => "C",
-                        Tone::Cs => "Cs",
-                        Tone::D => "D",
-                        Tone::Ds => "Ds",
-                        Tone::E => "E",
-                        Tone::F => "F",
-                        Tone::Fs => "Fs",
-                        Tone::G => "G",
-                        Tone::Gs => "Gs",
-                        Tone::A => "A",
-                        Tone::As => "As",
-                        Tone::B => "B",
-                    };
-                    
-                    note_strs.push(format!("{}{}-{}", 
-                        tone_str,
-                        note.pitch.octave,
-                        note.duration_b32
-                    ));
-                }
-                
-                writeln!(file, "{}: {}", time, note_strs.join(" "))?;
+
+        // Write key, e.g. "KEY: Fs minor" or "KEY: C custom 0,3,5,7,10"
+        match score.key.scale {
+            Scale::Custom(_) => {
+                let intervals: Vec<String> = score
+                    .key
+                    .scale
+                    .intervals()
+                    .iter()
+                    .map(|interval| interval.to_string())
+                    .collect();
+                writeln!(file, "KEY: {} custom {}", tone_file_str(score.key.tonic), intervals.join(","))?;
+            }
+            scale => writeln!(file, "KEY: {} {}", tone_file_str(score.key.tonic), scale.as_str())?,
+        }
+
+        // Write groove, e.g. "GROOVE: 1/16 30 laid_back", then "TRACK_SWING: 0 45" per override
+        let groove = &score.groove;
+        writeln!(
+            file,
+            "GROOVE: {} {} {}",
+            groove.swing_unit.as_str(),
+            groove.swing_percent,
+            groove.template.as_str()
+        )?;
+        for (track, swing) in groove.track_swing.iter().enumerate() {
+            if let Some(swing) = swing {
+                writeln!(file, "TRACK_SWING: {} {}", track, swing)?;
             }
         }
         
+        // Write notes
+        write_note_lines(&mut file, score)?;
+
+        // Write patterns as blocks of note lines, then the clips that place them,
+        // e.g. "CLIP: P1 128 4 -2" for four repeats a whole tone down
+        for pattern in &score.arrangement.patterns {
+            writeln!(file, "PATTERN: {} {}", pattern.name, pattern.length_b32)?;
+            write_note_lines(&mut file, &pattern.score)?;
+            writeln!(file, "END_PATTERN")?;
+        }
+        for clip in &score.arrangement.clips {
+            writeln!(file, "CLIP: {} {} {} {}", clip.pattern, clip.start_b32, clip.repeat, clip.transpose)?;
+        }
+
+        // Write markers, e.g. "MARKER: 128 Chorus", each starting a section
+        for marker in &score.markers {
+            writeln!(file, "MARKER: {} {}", marker.time_b32, marker.name)?;
+        }
+
+        // Write the song length when set, e.g. "LENGTH: 256"
+        if let Some(length_b32) = score.length_b32 {
+            writeln!(file, "LENGTH: {}", length_b32)?;
+        }
+
+        // Write the loop, e.g. "LOOP: 32 96 on"
+        if let Some((start, end)) = loop_state.region() {
+            let mode = if loop_state.mode == LoopMode::Looping { "on" } else { "off" };
+            writeln!(file, "LOOP: {} {} {}", start, end, mode)?;
+        }
+
         self.current_path = Some(path);
         Ok(())
     }
 
-    pub fn load(path: PathBuf) -> io::Result<Score> {
-        let mut score = Score {
-            bpm: 120,
-            notes: HashMap::new(),
-            active_notes: HashMap::new(),
-        };
+    // Writes the song as a MIDI file beside the song file, returning its path.
+    pub fn export_midi(&self, score: &Score) -> io::Result<PathBuf> {
+        let path = self
+            .current_path
+            .clone()
+            .unwrap_or_else(|| self.generate_default_filename())
+            .with_extension("mid");
+        fs::write(&path, midi_file::song_to_midi(score))?;
+        Ok(path)
+    }
+
+    // The loop isn't part of the score, so it's read alongside it.
+    pub fn load(path: PathBuf) -> io::Result<(Score, LoopState)> {
+        let mut score = Score::new(120);
+        let mut loop_state = LoopState::new();
+        let mut arrangement = Arrangement::new();
+        // Pattern whose note lines are being read
+        let mut pattern: Option<Pattern> = None;
 
         let file = File::open(&path)?;
         let reader = BufReader::new(file);
 
         for line in reader.lines() {
             let line = line?.trim().to_string();
-            if line.starts_with("BPM:") {
+            if let Some(pattern_str) = line.strip_prefix("PATTERN:") {
+                pattern = Some(parse_pattern(pattern_str, score.bpm)?);
+            } else if line == "END_PATTERN" {
+                if let Some(pattern) = pattern.take() {
+                    arrangement = arrangement.with_pattern(pattern);
+                }
+            } else if let Some(clip_str) = line.strip_prefix("CLIP:") {
+                arrangement = arrangement.with_clip(parse_clip(clip_str)?);
+            } else if let Some(pattern) = pattern.as_mut() {
+                if !line.is_empty() {
+                    parse_note_line(&line, &mut pattern.score)?;
+                }
+            } else if line.starts_with("BPM:") {
                 score.bpm = line[4..].trim().parse().expect("Invalid BPM format");
+            } else if let Some(key_str) = line.strip_prefix("KEY:") {
+                score.key = parse_key(key_str)?;
+            } else if let Some(marker_str) = line.strip_prefix("MARKER:") {
+                let marker = parse_marker(marker_str)?;
+                score.set_marker(marker.time_b32, &marker.name);
+            } else if let Some(length_str) = line.strip_prefix("LENGTH:") {
+                let length_b32 = length_str.trim().parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid length"))?;
+                score.length_b32 = Some(length_b32);
+            } else if let Some(loop_str) = line.strip_prefix("LOOP:") {
+                loop_state = parse_loop(loop_str)?;
+            } else if let Some(groove_str) = line.strip_prefix("GROOVE:") {
+                score.groove = parse_groove(groove_str, &score.groove)?;
+            } else if let Some(track_swing_str) = line.strip_prefix("TRACK_SWING:") {
+                score.groove = parse_track_swing(track_swing_str, &score.groove)?;
             } else if !line.is_empty() {
-                let parts: Vec<&str> = line.split(':').map(|s| s.trim()).collect();
-                if parts.len() == 2 {
-                    let onset: u64 = parts[0].parse().expect("Invalid onset format");
-                    let notes_str: Vec<&str> = parts[1].split_whitespace().collect();
-
-                    for note_str in notes_str {
-                        let note_parts: Vec<&str> = note_str.split('-').collect();
-                        if note_parts.len() == 2 {
-                            let tone_octave = &note_parts[0];
-                            let duration: u64 = note_parts[1].parse().expect("Invalid duration format");
-
-                            let tone = match &tone_octave[..tone_octave.len() - 1] {
-                                "C" => Tone::C,
-                                "Cs" => Tone::Cs,
-                                "D" => Tone::D,
-                                "Ds" => Tone::Ds,
-                                "E" => Tone::E,
-                                "F" => Tone::F,
-                                "Fs" => Tone::Fs,
-                                "G" => Tone::G,
-                                "Gs" => Tone::Gs,
-                                "A" => Tone::A,
-                                "As" => Tone::As,
-                                "B" => Tone::B,
-                                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid tone")),
-                            };
-
-                            let octave: u8 = tone_octave[tone_octave.len() - 1..]
-                                .parse()
-                                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid octave"))?;
-
-                            score.insert_or_remove(
-                                Pitch::new(tone, octave as u16),
-                                onset,
-                                duration
-                            );
-                        }
-                    }
-                }
+                parse_note_line(&line, &mut score)?;
             }
         }
 
+        score.set_arrangement(arrangement);
+        Ok((score, loop_state))
+    }
+
+    // Notes in the song line format with onsets relative to the first note, for
+    // moving phrases through the system clipboard.
+    pub fn notes_to_text(score: &Score) -> String {
+        let mut text = Vec::new();
+        write_note_lines(&mut text, &score.translate(Some(0))).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    // Parses note lines, skipping anything else, so a whole song file can be pasted.
+    pub fn notes_from_text(text: &str) -> io::Result<Score> {
+        let mut score = Score::new(120);
+        // Terminals send pasted line breaks as carriage returns
+        for line in text.split(['\n', '\r']) {
+            let line = line.trim();
+            if line.starts_with(|c: char| c.is_ascii_digit()) {
+                parse_note_line(line, &mut score)?;
+            }
+        }
         Ok(score)
     }
-} 
\ No newline at end of file
+}
+
+fn write_note_lines(writer: &mut impl Write, score: &Score) -> io::Result<()> {
+    let mut sorted_times: Vec<_> = score.notes.keys().collect();
+    sorted_times.sort();
+
+    for &time in sorted_times {
+        if let Some(notes) = score.notes.get(&time) {
+            let mut note_strs = Vec::new();
+
+            for note in notes {
+                let mut note_str = format!("{}{}-{}",
+                    tone_file_str(note.pitch.tone),
+                    note.pitch.octave,
+                    note.duration_b32
+                );
+                // Velocity is only written when it differs from the default, e.g. "C4-8-90"
+                if note.velocity != DEFAULT_VELOCITY {
+                    note_str.push_str(&format!("-{}", note.velocity));
+                }
+                note_strs.push(note_str);
+            }
+
+            writeln!(writer, "{}: {}", time, note_strs.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+// Parses a note line such as "16: As3-8 D4-8-90" into `score`.
+fn parse_note_line(line: &str, score: &mut Score) -> io::Result<()> {
+    let invalid_note = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let parts: Vec<&str> = line.split(':').map(|s| s.trim()).collect();
+    if parts.len() != 2 {
+        return Ok(());
+    }
+
+    let onset: u64 = parts[0].parse().map_err(|_| invalid_note("Invalid onset format"))?;
+    for note_str in parts[1].split_whitespace() {
+        let note_parts: Vec<&str> = note_str.split('-').collect();
+        if note_parts.len() == 2 || note_parts.len() == 3 {
+            let tone_octave = &note_parts[0];
+            if tone_octave.len() < 2 || !tone_octave.is_ascii() {
+                return Err(invalid_note("Invalid note"));
+            }
+            let duration: u64 = note_parts[1].parse().map_err(|_| invalid_note("Invalid duration format"))?;
+
+            let tone = parse_tone(&tone_octave[..tone_octave.len() - 1])?;
+
+            let octave: u8 = tone_octave[tone_octave.len() - 1..]
+                .parse()
+                .map_err(|_| invalid_note("Invalid octave"))?;
+
+            let velocity: u8 = match note_parts.get(2) {
+                Some(velocity_str) => velocity_str.parse().map_err(|_| invalid_note("Invalid velocity"))?,
+                None => DEFAULT_VELOCITY,
+            };
+
+            score.insert_or_remove_note(Note {
+                pitch: Pitch::new(tone, octave as u16),
+                onset_b32: onset,
+                duration_b32: duration.max(1),
+                velocity: velocity.clamp(1, 127),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn tone_file_str(tone: Tone) -> &'static str {
+    match tone {
+        Tone::C => "C",
+        Tone::Cs => "Cs",
+        Tone::D => "D",
+        Tone::Ds => "Ds",
+        Tone::E => "E",
+        Tone::F => "F",
+        Tone::Fs => "Fs",
+        Tone::G => "G",
+        Tone::Gs => "Gs",
+        Tone::A => "A",
+        Tone::As => "As",
+        Tone::B => "B",
+    }
+}
+
+fn parse_tone(tone_str: &str) -> io::Result<Tone> {
+    match tone_str {
+        "C" => Ok(Tone::C),
+        "Cs" => Ok(Tone::Cs),
+        "D" => Ok(Tone::D),
+        "Ds" => Ok(Tone::Ds),
+        "E" => Ok(Tone::E),
+        "F" => Ok(Tone::F),
+        "Fs" => Ok(Tone::Fs),
+        "G" => Ok(Tone::G),
+        "Gs" => Ok(Tone::Gs),
+        "A" => Ok(Tone::A),
+        "As" => Ok(Tone::As),
+        "B" => Ok(Tone::B),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid tone")),
+    }
+}
+
+fn parse_groove(groove_str: &str, groove: &Groove) -> io::Result<Groove> {
+    let invalid_groove = || io::Error::new(io::ErrorKind::InvalidData, "Invalid groove");
+    let parts: Vec<&str> = groove_str.split_whitespace().collect();
+    if parts.len() != 3 {
+        return Err(invalid_groove());
+    }
+
+    let mut new_groove = groove.clone();
+    new_groove.swing_unit = SwingUnit::from_name(parts[0]).ok_or_else(invalid_groove)?;
+    new_groove.swing_percent = parts[1].parse().map_err(|_| invalid_groove())?;
+    new_groove.template = GrooveTemplate::from_name(parts[2]).ok_or_else(invalid_groove)?;
+    Ok(new_groove)
+}
+
+fn parse_track_swing(track_swing_str: &str, groove: &Groove) -> io::Result<Groove> {
+    let invalid_swing = || io::Error::new(io::ErrorKind::InvalidData, "Invalid track swing");
+    let parts: Vec<&str> = track_swing_str.split_whitespace().collect();
+    if parts.len() != 2 {
+        return Err(invalid_swing());
+    }
+
+    let track: usize = parts[0].parse().map_err(|_| invalid_swing())?;
+    let swing: u8 = parts[1].parse().map_err(|_| invalid_swing())?;
+    Ok(groove.set_track_swing(track, Some(swing)))
+}
+
+fn parse_pattern(pattern_str: &str, bpm: u16) -> io::Result<Pattern> {
+    let invalid_pattern = || io::Error::new(io::ErrorKind::InvalidData, "Invalid pattern");
+    let parts: Vec<&str> = pattern_str.split_whitespace().collect();
+    if parts.len() != 2 {
+        return Err(invalid_pattern());
+    }
+
+    Ok(Pattern {
+        name: parts[0].to_string(),
+        length_b32: parts[1].parse().map_err(|_| invalid_pattern())?,
+        score: Score::new(bpm),
+    })
+}
+
+fn parse_marker(marker_str: &str) -> io::Result<Marker> {
+    let invalid_marker = || io::Error::new(io::ErrorKind::InvalidData, "Invalid marker");
+    // The name is the rest of the line and may hold spaces
+    let (time_str, name) = marker_str.trim().split_once(' ').ok_or_else(invalid_marker)?;
+    let time_b32 = time_str.parse().map_err(|_| invalid_marker())?;
+    Ok(Marker::new(time_b32, name.trim()))
+}
+
+fn parse_loop(loop_str: &str) -> io::Result<LoopState> {
+    let invalid_loop = || io::Error::new(io::ErrorKind::InvalidData, "Invalid loop");
+    let parts: Vec<&str> = loop_str.split_whitespace().collect();
+    if parts.len() != 3 {
+        return Err(invalid_loop());
+    }
+    let start: u64 = parts[0].parse().map_err(|_| invalid_loop())?;
+    let end: u64 = parts[1].parse().map_err(|_| invalid_loop())?;
+    let mode = match parts[2] {
+        "on" => LoopMode::Looping,
+        "off" => LoopMode::Disabled,
+        _ => return Err(invalid_loop()),
+    };
+    Ok(LoopState::new().set_region(start, end).set_mode(mode))
+}
+
+fn parse_clip(clip_str: &str) -> io::Result<Clip> {
+    let invalid_clip = || io::Error::new(io::ErrorKind::InvalidData, "Invalid clip");
+    let parts: Vec<&str> = clip_str.split_whitespace().collect();
+    if parts.len() != 4 {
+        return Err(invalid_clip());
+    }
+
+    Ok(Clip {
+        pattern: parts[0].to_string(),
+        start_b32: parts[1].parse().map_err(|_| invalid_clip())?,
+        repeat: parts[2].parse::<u64>().map_err(|_| invalid_clip())?.max(1),
+        transpose: parts[3].parse().map_err(|_| invalid_clip())?,
+    })
+}
+
+fn parse_key(key_str: &str) -> io::Result<Key> {
+    let invalid_key = || io::Error::new(io::ErrorKind::InvalidData, "Invalid key");
+    let parts: Vec<&str> = key_str.split_whitespace().collect();
+    if parts.len() < 2 {
+        return Err(invalid_key());
+    }
+
+    let tonic = parse_tone(parts[0])?;
+    let scale = if parts[1] == "custom" {
+        let mut mask = 0;
+        for interval in parts.get(2).ok_or_else(invalid_key)?.split(',') {
+            let interval: u16 = interval.parse().map_err(|_| invalid_key())?;
+            if interval > 11 {
+                return Err(invalid_key());
+            }
+            mask |= 1 << interval;
+        }
+        Scale::Custom(mask)
+    } else {
+        Scale::from_name(parts[1]).ok_or_else(invalid_key)?
+    };
+    Ok(Key::new(tonic, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notes_text_round_trip() {
+        let mut score = Score::new(120);
+        score.insert_note(Note {
+            pitch: Pitch::new(Tone::C, 4),
+            onset_b32: 32,
+            duration_b32: 8,
+            velocity: DEFAULT_VELOCITY,
+        });
+        score.insert_note(Note {
+            pitch: Pitch::new(Tone::Fs, 3),
+            onset_b32: 40,
+            duration_b32: 4,
+            velocity: 90,
+        });
+
+        let text = SongFile::notes_to_text(&score);
+        assert_eq!(text, "0: C4-8\n8: Fs3-4-90\n");
+
+        let pasted = SongFile::notes_from_text(&format!("BPM: 100\n{}", text)).unwrap();
+        assert_eq!(pasted.notes_starting_at_time(8)[0].velocity, 90);
+        assert_eq!(pasted.notes_starting_at_time(0)[0].duration_b32, 8);
+        assert!(SongFile::notes_from_text("0: Cx4-8").is_err());
+    }
+}
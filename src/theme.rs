@@ -0,0 +1,198 @@
+use crossterm::style::{available_color_count, Attribute, Color};
+use std::env;
+
+use crate::draw_components::Style;
+use crate::score::NoteState;
+
+const TRACK_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ThemeMode {
+    Color,
+    Monochrome,
+}
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    pub border: Style,
+    pub grid: Style,
+    pub bar_line: Style,
+    pub ruler: Style,
+    pub pitch_label: Style,
+    pub pitch_label_tonic: Style,
+    pub pitch_label_out_of_key: Style,
+    pub selection: Style,
+    pub selection_buffer: Style,
+    pub cursor: Style,
+    pub playhead: Style,
+    pub loop_region: Style,
+    pub loop_shade: Style,
+    pub marker: Style,
+    pub status_bar: Style,
+    pub panel_tab: Style,
+    pub panel_tab_active: Style,
+    pub overview_viewport: Style,
+}
+
+impl Theme {
+    pub fn color() -> Theme {
+        Theme {
+            mode: ThemeMode::Color,
+            border: Style::fg(Color::DarkGrey),
+            grid: Style::fg(Color::DarkGrey),
+            bar_line: Style::fg(Color::Grey),
+            ruler: Style::fg(Color::Grey),
+            pitch_label: Style::fg(Color::White),
+            pitch_label_tonic: Style::fg(Color::Yellow).with(Attribute::Bold),
+            pitch_label_out_of_key: Style::fg(Color::DarkGrey),
+            selection: Style::bg(Color::DarkBlue),
+            selection_buffer: Style::fg(Color::Magenta).with(Attribute::Bold),
+            cursor: Style::fg(Color::Black).on(Color::White),
+            playhead: Style::bg(Color::DarkGreen),
+            loop_region: Style::bg(Color::DarkYellow),
+            loop_shade: Style::bg(Color::DarkGrey),
+            marker: Style::fg(Color::Yellow).with(Attribute::Bold),
+            status_bar: Style::fg(Color::Black).on(Color::Grey),
+            panel_tab: Style::fg(Color::Grey),
+            panel_tab_active: Style::fg(Color::White).with(Attribute::Bold),
+            overview_viewport: Style::bg(Color::DarkGrey),
+        }
+    }
+
+    // Attributes only, for terminals without color or when NO_COLOR is set.
+    pub fn monochrome() -> Theme {
+        Theme {
+            mode: ThemeMode::Monochrome,
+            border: Style::default(),
+            grid: Style::default(),
+            bar_line: Style::default(),
+            ruler: Style::default(),
+            pitch_label: Style::default(),
+            pitch_label_tonic: Style::default().with(Attribute::Bold),
+            pitch_label_out_of_key: Style::default().with(Attribute::Dim),
+            selection: Style::default().with(Attribute::Underlined),
+            selection_buffer: Style::default().with(Attribute::Bold),
+            cursor: Style::default().with(Attribute::Reverse),
+            // Unlike the cursor, so the cursor still shows on the playhead
+            playhead: Style::default().with(Attribute::Bold).with(Attribute::Underlined),
+            loop_region: Style::default().with(Attribute::Bold),
+            loop_shade: Style::default().with(Attribute::Dim),
+            marker: Style::default().with(Attribute::Bold),
+            status_bar: Style::default().with(Attribute::Reverse),
+            panel_tab: Style::default(),
+            panel_tab_active: Style::default().with(Attribute::Bold),
+            overview_viewport: Style::default().with(Attribute::Underlined),
+        }
+    }
+
+    pub fn detect() -> Theme {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let dumb_term = env::var("TERM").is_ok_and(|term| term == "dumb");
+        if no_color || dumb_term || available_color_count() < 8 {
+            Theme::monochrome()
+        } else {
+            Theme::color()
+        }
+    }
+
+    pub fn toggle_mode(&self) -> Theme {
+        match self.mode {
+            ThemeMode::Color => Theme::monochrome(),
+            ThemeMode::Monochrome => Theme::color(),
+        }
+    }
+
+    pub fn track_color(&self, track: usize) -> Option<Color> {
+        match self.mode {
+            ThemeMode::Color => Some(TRACK_COLORS[track % TRACK_COLORS.len()]),
+            ThemeMode::Monochrome => None,
+        }
+    }
+
+    pub fn note(&self, track: usize, state: NoteState) -> Style {
+        let style = match self.track_color(track) {
+            Some(color) => Style::fg(color),
+            None => Style::default(),
+        };
+        match state {
+            NoteState::Onset => style.with(Attribute::Bold),
+            NoteState::Sustain => style,
+            NoteState::Release => style.with(Attribute::Dim),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monochrome_has_no_colors() {
+        let theme = Theme::monochrome();
+        let styles = [
+            theme.border,
+            theme.grid,
+            theme.bar_line,
+            theme.ruler,
+            theme.pitch_label,
+            theme.pitch_label_tonic,
+            theme.pitch_label_out_of_key,
+            theme.selection,
+            theme.selection_buffer,
+            theme.cursor,
+            theme.playhead,
+            theme.loop_region,
+            theme.loop_shade,
+            theme.marker,
+            theme.status_bar,
+            theme.panel_tab,
+            theme.panel_tab_active,
+            theme.overview_viewport,
+        ];
+        for style in styles {
+            assert_eq!(style.fg, None);
+            assert_eq!(style.bg, None);
+        }
+        assert_eq!(theme.track_color(0), None);
+        assert_eq!(theme.track_color(7), None);
+    }
+
+    #[test]
+    fn test_monochrome_attributes() {
+        let theme = Theme::monochrome();
+        let onset = theme.note(0, NoteState::Onset);
+        let sustain = theme.note(0, NoteState::Sustain);
+        let release = theme.note(0, NoteState::Release);
+        assert_eq!(onset.fg, None);
+        assert!(onset.attributes.has(Attribute::Bold));
+        assert_eq!(sustain, Style::default());
+        assert!(release.attributes.has(Attribute::Dim));
+        // Without colors the cursor and selection must still stand out
+        assert!(theme.cursor.attributes.has(Attribute::Reverse));
+        assert!(theme.selection.attributes.has(Attribute::Underlined));
+        assert_ne!(theme.cursor, theme.grid);
+        assert_ne!(theme.selection, theme.grid);
+        assert_ne!(theme.cursor, theme.playhead);
+        assert_ne!(theme.loop_region, theme.loop_shade);
+    }
+
+    #[test]
+    fn test_toggle_mode() {
+        let theme = Theme::color();
+        assert!(theme.mode == ThemeMode::Color);
+        assert_eq!(theme.track_color(0), Some(Color::Cyan));
+        assert_eq!(theme.track_color(6), Some(Color::Cyan));
+        let theme = theme.toggle_mode();
+        assert!(theme.mode == ThemeMode::Monochrome);
+        assert_eq!(theme.note(1, NoteState::Onset).fg, None);
+        assert!(theme.toggle_mode().mode == ThemeMode::Color);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::pitch::Pitch;
+use crate::quantize::{Humanize, Quantize};
+use crate::scale::Key;
+use crate::score::Note;
+use crate::selection_range::SelectionRange;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Transpose(i32),         // Semitones
+    TransposeDiatonic(i32), // Scale degrees in the song key
+    Invert,                 // Mirror pitches inside the selected pitch range
+    Retrograde,             // Reverse in time inside the selected time range
+    Stretch(u64, u64),      // Scale onsets and durations by numerator / denominator
+    Quantize(Quantize),
+    Humanize(Humanize),
+}
+
+impl Transform {
+    // Transformed note, or the original when the result would leave the pitch range.
+    // `span_end_b32` is the later of the selection end and the end of its last note.
+    pub fn apply(&self, note: Note, selection_range: SelectionRange, span_end_b32: u64, key: Key) -> Note {
+        let mut new_note = note;
+        match *self {
+            Transform::Transpose(semitones) => {
+                if let Some(pitch) = note.pitch.transpose(semitones) {
+                    new_note.pitch = pitch;
+                }
+            }
+            Transform::TransposeDiatonic(steps) => {
+                if let Some(pitch) = key.transpose_diatonic(note.pitch, steps) {
+                    new_note.pitch = pitch;
+                }
+            }
+            Transform::Invert => {
+                let axis_sum = selection_range.pitch_low.index() + selection_range.pitch_high.index();
+                if let Some(pitch) = axis_sum
+                    .checked_sub(note.pitch.index())
+                    .and_then(Pitch::from_index)
+                {
+                    new_note.pitch = pitch;
+                }
+            }
+            Transform::Retrograde => {
+                let note_end = note.onset_b32 + note.duration_b32;
+                new_note.onset_b32 = selection_range.time_point_start_b32 + span_end_b32 - note_end;
+            }
+            Transform::Stretch(numerator, denominator) => {
+                // Signed, as overlap selections can hold notes starting before the range
+                let start = selection_range.time_point_start_b32 as i64;
+                let offset = (note.onset_b32 as i64 - start) * numerator as i64 / denominator as i64;
+                new_note.onset_b32 = (start + offset).max(0) as u64;
+                new_note.duration_b32 = (note.duration_b32 * numerator / denominator).max(1);
+            }
+            Transform::Quantize(quantize) => new_note = quantize.apply(note),
+            Transform::Humanize(humanize) => new_note = humanize.apply(note),
+        }
+        new_note
+    }
+}
@@ -0,0 +1,57 @@
+// How much time one score column shows. Independent from `Resolution`, which is the
+// edit grid used for cursor steps and note insertion.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Zoom {
+    Time1_32,
+    Time1_16,
+    Time1_8,
+    Time1_4,
+    Time1_2,
+    Time1_1,
+}
+
+impl Zoom {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Zoom::Time1_32 => "1/32",
+            Zoom::Time1_16 => "1/16",
+            Zoom::Time1_8 => "1/8",
+            Zoom::Time1_4 => "1/4",
+            Zoom::Time1_2 => "1/2",
+            Zoom::Time1_1 => "1/1",
+        }
+    }
+
+    pub fn duration_b32(&self) -> u64 {
+        match self {
+            Zoom::Time1_32 => 1,
+            Zoom::Time1_16 => 2,
+            Zoom::Time1_8 => 4,
+            Zoom::Time1_4 => 8,
+            Zoom::Time1_2 => 16,
+            Zoom::Time1_1 => 32,
+        }
+    }
+
+    pub fn zoom_out(&self) -> Zoom {
+        match self {
+            Zoom::Time1_32 => Zoom::Time1_16,
+            Zoom::Time1_16 => Zoom::Time1_8,
+            Zoom::Time1_8 => Zoom::Time1_4,
+            Zoom::Time1_4 => Zoom::Time1_2,
+            Zoom::Time1_2 => Zoom::Time1_1,
+            Zoom::Time1_1 => Zoom::Time1_1,
+        }
+    }
+
+    pub fn zoom_in(&self) -> Zoom {
+        match self {
+            Zoom::Time1_1 => Zoom::Time1_2,
+            Zoom::Time1_2 => Zoom::Time1_4,
+            Zoom::Time1_4 => Zoom::Time1_8,
+            Zoom::Time1_8 => Zoom::Time1_16,
+            Zoom::Time1_16 => Zoom::Time1_32,
+            Zoom::Time1_32 => Zoom::Time1_32,
+        }
+    }
+}